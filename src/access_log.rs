@@ -0,0 +1,325 @@
+use crate::log_reader::rotated_from;
+use crate::parser::{parse_file_with_includes, ConfigFile};
+use crate::utils::nginx_conf_file;
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Predefined by nginx, used when `access_log` doesn't name a format.
+const COMBINED: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent""#;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Variable(String),
+}
+
+/// A `log_format`, used to split access log lines into their variables.
+#[derive(Debug, Clone)]
+pub struct LogFormat {
+    segments: Vec<Segment>,
+}
+
+/// One request of an access log. Fields are `None` when the format doesn't log them
+/// or nginx logged `-`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AccessEntry {
+    pub remote_addr: Option<IpAddr>,
+    pub time: Option<DateTime<FixedOffset>>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub protocol: Option<String>,
+    pub status: Option<u16>,
+    pub bytes: Option<u64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub host: Option<String>,
+    /// `$request_time` in seconds.
+    pub request_time: Option<f64>,
+    /// Every variable of the format, as logged.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl LogFormat {
+    pub fn new(format: &str) -> Self {
+        Self {
+            segments: segments(format),
+        }
+    }
+
+    pub fn combined() -> Self {
+        Self::new(COMBINED)
+    }
+
+    /// Whether the format logs the variable `name`, e.g. `host`.
+    pub fn logs(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.segments
+            .iter()
+            .any(|x| matches!(x, Segment::Variable(variable) if variable == name))
+    }
+
+    /// Splits `line` into the variables of the format, `None` when it doesn't fit.
+    pub fn parse(
+        &self,
+        line: &str,
+    ) -> Option<AccessEntry> {
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        let mut rest = line;
+
+        for (index, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                Segment::Variable(name) => {
+                    // A variable ends where the literal after it starts, or at the next
+                    // space when two variables are logged back to back.
+                    let end = match self.segments.get(index + 1) {
+                        Some(Segment::Literal(next)) => rest.find(next.as_str())?,
+                        Some(Segment::Variable(_)) => rest.find(' ').unwrap_or(rest.len()),
+                        None => rest.len(),
+                    };
+
+                    fields.insert(name.clone(), rest[..end].to_string());
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        if !rest.trim().is_empty() {
+            return None;
+        }
+
+        Some(AccessEntry::from_fields(fields))
+    }
+}
+
+fn segments(format: &str) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = vec![];
+    let mut literal = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        if char != '$' {
+            literal.push(char);
+            continue;
+        }
+
+        // `${name}` is used when a name is directly followed by letters.
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+
+        while let Some(char) = chars.next_if(|x| x.is_ascii_alphanumeric() || *x == '_') {
+            name.push(char);
+        }
+
+        if braced {
+            chars.next_if_eq(&'}');
+        }
+
+        if name.is_empty() {
+            literal.push('$');
+            continue;
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(std::mem::take(&mut literal)));
+        }
+
+        segments.push(Segment::Variable(name));
+    }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    segments
+}
+
+impl AccessEntry {
+    fn from_fields(fields: BTreeMap<String, String>) -> Self {
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .filter(|x| !x.is_empty() && *x != "-")
+                .cloned()
+        };
+        let first = |names: &[&str]| names.iter().find_map(|x| field(x));
+
+        let mut entry = Self {
+            remote_addr: field("remote_addr").and_then(|x| x.parse().ok()),
+            time: parse_time(&fields),
+            method: field("request_method"),
+            path: first(&["request_uri", "uri"]),
+            protocol: field("server_protocol"),
+            status: field("status").and_then(|x| x.parse().ok()),
+            bytes: first(&["body_bytes_sent", "bytes_sent"]).and_then(|x| x.parse().ok()),
+            referer: field("http_referer"),
+            user_agent: field("http_user_agent"),
+            host: first(&["host", "http_host", "server_name"]),
+            request_time: field("request_time").and_then(|x| x.parse().ok()),
+            fields: BTreeMap::new(),
+        };
+
+        // `GET /index.html HTTP/1.1`
+        if let Some(request) = field("request") {
+            let mut parts = request.splitn(3, ' ');
+
+            entry.method = entry.method.or(parts.next().map(String::from));
+            entry.path = entry.path.or(parts.next().map(String::from));
+            entry.protocol = entry.protocol.or(parts.next().map(String::from));
+        }
+
+        entry.fields = fields;
+        entry
+    }
+}
+
+fn parse_time(fields: &BTreeMap<String, String>) -> Option<DateTime<FixedOffset>> {
+    if let Some(time) = fields.get("time_local") {
+        return DateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S %z").ok();
+    }
+
+    if let Some(time) = fields.get("time_iso8601") {
+        return DateTime::parse_from_rfc3339(time).ok();
+    }
+
+    // Seconds with milliseconds, e.g. `1697623200.123`.
+    let msec: f64 = fields.get("msec")?.parse().ok()?;
+    let time = Utc.timestamp_millis_opt((msec * 1000.0) as i64).single()?;
+
+    Some(time.fixed_offset())
+}
+
+/// `combined` plus every `log_format` of the nginx config.
+fn log_formats(config: &ConfigFile) -> BTreeMap<String, LogFormat> {
+    let mut formats = BTreeMap::from([("combined".to_string(), LogFormat::combined())]);
+
+    for directive in config.find_all("log_format") {
+        let Some((name, args)) = directive.args.split_first() else {
+            continue;
+        };
+
+        let format: String = args
+            .iter()
+            .filter(|x| !x.starts_with("escape="))
+            .map(|x| x.as_str())
+            .collect();
+
+        formats.insert(name.clone(), LogFormat::new(&format));
+    }
+
+    formats
+}
+
+/// Format of the `access_log` writing to `file_path`, or to the file it was rotated
+/// from (e.g. `access.log.2.gz`). Falls back to `combined`.
+pub fn log_format_for(file_path: &Path) -> LogFormat {
+    let Ok(config) = parse_file_with_includes(Path::new(&nginx_conf_file())) else {
+        return LogFormat::combined();
+    };
+
+    let formats = log_formats(&config);
+    let live_path = rotated_from(file_path);
+
+    config
+        .find_all("access_log")
+        .into_iter()
+        .find(|x| x.arg(0).is_some_and(|path| Path::new(path) == live_path))
+        .and_then(|x| formats.get(x.arg(1).unwrap_or("combined")))
+        .cloned()
+        .unwrap_or_else(LogFormat::combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use std::path::PathBuf;
+
+    fn formats(source: &str) -> BTreeMap<String, LogFormat> {
+        log_formats(&ConfigFile {
+            path: PathBuf::from("nginx.conf"),
+            nodes: parse(source).unwrap(),
+        })
+    }
+
+    #[test]
+    fn parses_combined() {
+        let line = r#"10.0.0.7 - - [18/Oct/2026:09:12:44 +0200] "GET /index.html?a=1 HTTP/1.1" 200 612 "https://example.com/" "Mozilla/5.0 (X11; Linux x86_64)""#;
+        let entry = LogFormat::combined().parse(line).unwrap();
+
+        assert_eq!(entry.remote_addr, Some("10.0.0.7".parse().unwrap()));
+        assert_eq!(
+            entry.time.unwrap().to_rfc3339(),
+            "2026-10-18T09:12:44+02:00"
+        );
+        assert_eq!(entry.method.as_deref(), Some("GET"));
+        assert_eq!(entry.path.as_deref(), Some("/index.html?a=1"));
+        assert_eq!(entry.protocol.as_deref(), Some("HTTP/1.1"));
+        assert_eq!(entry.status, Some(200));
+        assert_eq!(entry.bytes, Some(612));
+        assert_eq!(entry.referer.as_deref(), Some("https://example.com/"));
+        assert_eq!(
+            entry.user_agent.as_deref(),
+            Some("Mozilla/5.0 (X11; Linux x86_64)")
+        );
+        assert_eq!(entry.fields["remote_user"], "-");
+    }
+
+    #[test]
+    fn dashes_are_missing_values() {
+        let line = r#"10.0.0.7 - - [18/Oct/2026:09:12:44 +0000] "GET / HTTP/1.1" 200 0 "-" "-""#;
+        let entry = LogFormat::combined().parse(line).unwrap();
+
+        assert_eq!(entry.referer, None);
+        assert_eq!(entry.user_agent, None);
+    }
+
+    #[test]
+    fn rejects_lines_of_other_formats() {
+        let format = LogFormat::combined();
+
+        assert!(format
+            .parse("2026/10/18 09:12:44 [error] 1#1: oops")
+            .is_none());
+        assert!(format
+            .parse(r#"10.0.0.7 - - [18/Oct/2026:09:12:44 +0000] "GET / HTTP/1.1" 200"#)
+            .is_none());
+    }
+
+    #[test]
+    fn reads_quoted_log_formats() {
+        let formats = formats(
+            r#"http {
+                log_format main escape=json '$remote_addr [$time_iso8601] "$request" '
+                                '$status "$http_user_agent" "$host" rt=$request_time ${upstream_cache_status}x';
+            }"#,
+        );
+
+        let main = &formats["main"];
+        let line = r#"10.0.0.7 [2026-10-18T09:12:44+00:00] "POST /api HTTP/2.0" 201 "curl/8.0" "example.com" rt=0.125 HITx"#;
+        let entry = main.parse(line).unwrap();
+
+        assert!(main.logs("host"));
+        assert!(formats.contains_key("combined"));
+        assert_eq!(entry.method.as_deref(), Some("POST"));
+        assert_eq!(entry.status, Some(201));
+        assert_eq!(entry.host.as_deref(), Some("example.com"));
+        assert_eq!(entry.request_time, Some(0.125));
+        assert_eq!(entry.fields["upstream_cache_status"], "HIT");
+        assert!(entry.time.is_some());
+    }
+
+    #[test]
+    fn reads_msec_times() {
+        let entry = LogFormat::new("$msec $status")
+            .parse("1697623200.123 200")
+            .unwrap();
+
+        assert_eq!(entry.time.unwrap().timestamp_millis(), 1697623200123);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::error_log::Level;
+use crate::ng_export::ExportFormat;
+use crate::ng_scanners::SnippetKind;
+use crate::template::parse_key_val;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Manage nginx sites. Runs the interactive menu when no command is given.
+#[derive(Debug, Parser)]
+#[command(name = "ngsite", version, about)]
+pub struct Cli {
+    /// Use a profile from `[profiles.<name>]` in config.toml
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<NgCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NgCommand {
+    /// Create one or more default files (e.g. "nginx.conf", "proxy.com")
+    Defaults {
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Set a template variable (e.g. domain, www_domain, upstream_host, upstream_port,
+        /// web_root, access_log, error_log), unset ones use their default
+        #[arg(short = 's', long = "set", value_name = "KEY=VALUE", value_parser = parse_key_val)]
+        vars: Vec<(String, String)>,
+    },
+    /// Enable site(s), then test and reload nginx
+    Enable {
+        #[arg(required = true)]
+        sites: Vec<String>,
+        /// Enable even when a server_name or default_server conflicts with an enabled site
+        #[arg(long)]
+        force: bool,
+    },
+    /// Disable site(s), then test and reload nginx
+    Disable {
+        #[arg(required = true)]
+        sites: Vec<String>,
+    },
+    /// List sites with their server names, ports, targets and logs
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a site
+    View { site: String },
+    /// Edit a site
+    Edit { site: String },
+    /// Open log files in the pager, several files are shown as one stream, newest first
+    Logs {
+        #[arg(required_unless_present = "site", conflicts_with = "site")]
+        files: Vec<String>,
+        /// Open the logs a site writes to, read from its access_log and error_log
+        /// directives or inherited from nginx.conf
+        #[arg(short, long)]
+        site: Option<String>,
+        /// With --site, open its error logs instead of its access logs
+        #[arg(short, long, requires = "site")]
+        error: bool,
+        /// With --site, only show requests to its server names from logs it shares with
+        /// other sites
+        #[arg(long, requires = "site")]
+        by_host: bool,
+        /// Keep showing new lines, like `tail -f`, several logs can be followed at once
+        #[arg(short, long)]
+        follow: bool,
+        /// Include the logs rotated from these files, e.g. access.log.1 and access.log.2.gz
+        #[arg(short, long, conflicts_with = "follow")]
+        rotated: bool,
+        /// Only show requests matching a filter, e.g. "status>=500 and path~^/api".
+        /// Fields: status, method, path, ip, ua, referer, host, time, bytes,
+        /// request_time and $variable
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+    },
+    /// Summarize access logs: status codes, top paths, clients and user agents,
+    /// requests per minute and the slowest requests
+    Stats {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Include the logs rotated from these files, e.g. access.log.1 and access.log.2.gz
+        #[arg(short, long)]
+        rotated: bool,
+        /// Only count requests matching a filter, see `logs --filter`
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+        /// Number of rows of the top lists
+        #[arg(long, value_name = "N", default_value_t = 10)]
+        top: usize,
+        /// Print JSON instead of tables
+        #[arg(long)]
+        json: bool,
+    },
+    /// Group the entries of error logs by message, with counts and first/last seen
+    Errors {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Include the logs rotated from these files, e.g. error.log.1 and error.log.2.gz
+        #[arg(short, long)]
+        rotated: bool,
+        /// Minimum severity: debug, info, notice, warn, error, crit, alert or emerg
+        #[arg(short, long, default_value_t = Level::Debug)]
+        level: Level,
+        /// Pick a message to open its lines in the pager
+        #[arg(short, long, conflicts_with = "json")]
+        browse: bool,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write the requests of access logs as CSV or JSON Lines, oldest first
+    Export {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Include the logs rotated from these files, e.g. access.log.1 and access.log.2.gz
+        #[arg(short, long)]
+        rotated: bool,
+        /// Only export requests matching a filter, see `logs --filter`
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+        /// csv, or jsonl (ndjson) for one JSON object per request
+        #[arg(long, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Write to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Flag clients requesting probe paths, sending bad user agents or bursts of 4xx,
+    /// see `[scanners]` of the config
+    Scanners {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Include the logs rotated from these files, e.g. access.log.1 and access.log.2.gz
+        #[arg(short, long)]
+        rotated: bool,
+        /// Only check requests matching a filter, see `logs --filter`
+        #[arg(long, value_name = "EXPR")]
+        filter: Option<String>,
+        /// Block the flagged clients in nginxconfig.io/scanners.conf: deny, geo or map
+        #[arg(long, value_name = "KIND")]
+        snippet: Option<SnippetKind>,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find dangling, foreign and copied entries in sites-enabled
+    Doctor {
+        /// Fix every issue that can be fixed automatically
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Print a config file as a JSON syntax tree, defaults to nginx.conf
+    Parse {
+        file: Option<String>,
+        /// Don't follow `include` directives
+        #[arg(long)]
+        no_includes: bool,
+    },
+    /// Run `nginx -t`
+    Test,
+    /// Reload nginx
+    Reload,
+    /// Restart nginx
+    Restart,
+}
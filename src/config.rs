@@ -1,10 +1,15 @@
+use crate::layout::Layout;
+use crate::service::ServiceManager;
 use crate::utils::merge_config;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use directories::ProjectDirs;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
 
 const DEFAULT_CONFIG_STR: &str = include_str!("../default_config.toml");
 
@@ -28,19 +33,157 @@ fn init_config() -> Result<Config> {
         // macOS:   /Users/Alice/Library/Application Support/com.Foo-Corp.Bar-App
     }
 
+    resolve_profiles(&mut default_config);
+
     let config = serde_json::from_value(default_config)?;
 
     Ok(config)
 }
 
+/// Fills every profile with the top-level `paths` and `service` values it doesn't set.
+fn resolve_profiles(config: &mut Value) {
+    let base = json!({
+        "paths": config["paths"].clone(),
+        "service": config["service"].clone(),
+    });
+
+    if let Some(profiles) = config.get_mut("profiles").and_then(Value::as_object_mut) {
+        for profile in profiles.values_mut() {
+            let mut resolved = base.clone();
+            merge_config(&mut resolved, profile.take());
+            *profile = resolved;
+        }
+    }
+}
+
 lazy_static! {
     pub(crate) static ref CONFIG: Config = init_config().unwrap();
+    static ref ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// Selects the profile used by `Config::paths` and `Config::service`, `None` selects
+/// the top-level values.
+pub fn set_profile(name: Option<String>) -> Result<()> {
+    if let Some(name) = &name {
+        if !CONFIG.profiles.contains_key(name) {
+            return Err(anyhow!("Profile {name:?} not found in config"));
+        }
+    }
+
+    *ACTIVE_PROFILE.write().unwrap() = name;
+
+    Ok(())
+}
+
+pub fn active_profile() -> Option<String> {
+    ACTIVE_PROFILE.read().unwrap().clone()
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct Config {
-    pub paths: Paths,
+    paths: Paths,
+    #[serde(default)]
+    service: ServiceManager,
+    /// Named nginx instances, e.g. `[profiles.edge.paths]`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Substrings hiding every log line containing them.
+    #[serde(default)]
     pub ignore_values_in_log: Vec<String>,
+    /// Log lines hidden by the viewer, stats and error logs, e.g. `[[ignore]]`.
+    #[serde(default)]
+    pub ignore: Vec<IgnoreRule>,
+    #[serde(default)]
+    pub geoip: GeoIpDatabases,
+    #[serde(default)]
+    pub scanners: ScannerRules,
+    /// Lines shown by the log viewer, newest first.
+    #[serde(default = "default_max_log_lines")]
+    pub max_log_lines: usize,
+    /// Directory of user templates, defaults to `templates` in the config directory.
+    #[serde(default)]
+    pub templates: Option<String>,
+    /// Editor command with arguments, e.g. `code --wait`. Falls back to `$VISUAL`,
+    /// `$EDITOR` and `vi`.
+    #[serde(default)]
+    pub editor: Option<String>,
+}
+
+fn default_max_log_lines() -> usize {
+    100_000
+}
+
+impl Config {
+    /// Paths of the active profile.
+    pub fn paths(&self) -> &Paths {
+        match self.active() {
+            Some(profile) => &profile.paths,
+            None => &self.paths,
+        }
+    }
+
+    /// Service manager of the active profile.
+    pub fn service(&self) -> &ServiceManager {
+        match self.active() {
+            Some(profile) => &profile.service,
+            None => &self.service,
+        }
+    }
+
+    fn active(&self) -> Option<&Profile> {
+        active_profile().and_then(|name| self.profiles.get(&name))
+    }
+}
+
+/// Hides a log line when every condition it sets matches.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct IgnoreRule {
+    /// Client address or CIDR range, e.g. `10.0.0.0/8`.
+    pub ip: Option<String>,
+    /// Regex matched against the request path.
+    pub path: Option<String>,
+    /// Regex matched against the user agent.
+    pub user_agent: Option<String>,
+    /// `404`, `4xx` or `400-499`.
+    pub status: Option<String>,
+    /// Regex matched against the whole line.
+    pub line: Option<String>,
+}
+
+/// Local MaxMind databases, e.g. GeoLite2-Country.mmdb and GeoLite2-ASN.mmdb.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct GeoIpDatabases {
+    /// A Country or City database.
+    pub country: Option<String>,
+    pub asn: Option<String>,
+}
+
+/// What makes a client a scanner in the scanner report.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct ScannerRules {
+    /// Regexes matched against request paths.
+    pub probe_paths: Vec<String>,
+    /// Regexes matched against user agents.
+    pub bad_user_agents: Vec<String>,
+    /// 4xx responses to one client within a minute.
+    pub burst_4xx: u64,
+    /// Share of a bursting client's responses that are 4xx, from 0 to 1.
+    pub min_4xx_ratio: f64,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct Profile {
+    pub paths: Paths,
+    pub service: ServiceManager,
+}
+
+pub fn templates_dir() -> Option<PathBuf> {
+    match &CONFIG.templates {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => ProjectDirs::from_path("Ngsite".into())
+            .map(|proj_dirs| proj_dirs.config_dir().join("templates")),
+    }
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -49,4 +192,18 @@ pub struct Paths {
     pub sites_available: String,
     pub sites_enabled: String,
     pub logs: String,
+    /// Binary name on `PATH` or an absolute path, e.g. `/usr/local/openresty/bin/openresty`.
+    pub nginx_bin: String,
+    /// Passed as `-c`, defaults to nginx.conf in `nginx`.
+    #[serde(default)]
+    pub nginx_conf: Option<String>,
+    /// Passed as `-p` when set.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Look for sites in sub directories too, sites are then named by their path,
+    /// e.g. `clients/acme.conf`.
+    #[serde(default)]
+    pub recursive: bool,
 }
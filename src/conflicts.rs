@@ -0,0 +1,127 @@
+use crate::site::{Listen, SiteSummary};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Two sites nginx can't both serve as configured.
+#[derive(Debug, Clone)]
+pub enum Conflict {
+    /// nginx only warns "conflicting server name ... ignored" and picks one.
+    ServerName {
+        listen: String,
+        server_name: String,
+        sites: (String, String),
+    },
+    /// More than one `default_server` on the same address and port.
+    DefaultServer {
+        listen: String,
+        sites: (String, String),
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::ServerName {
+                listen,
+                server_name,
+                sites: (site, other),
+            } => write!(
+                f,
+                "{site}: server_name {server_name} on {listen} is already used by {other}"
+            ),
+            Self::DefaultServer {
+                listen,
+                sites: (site, other),
+            } => write!(
+                f,
+                "{site}: default_server on {listen} is already set by {other}"
+            ),
+        }
+    }
+}
+
+/// `*:80` and `0.0.0.0:80` are the same socket.
+fn listen_key(listen: &Listen) -> String {
+    let address = match listen.address.as_str() {
+        "0.0.0.0" => "*",
+        address => address,
+    };
+
+    if listen.port == 0 {
+        address.to_string()
+    } else {
+        format!("{address}:{}", listen.port)
+    }
+}
+
+/// Finds what `new_sites` would collide with, among `enabled` sites and each other.
+pub fn find_conflicts(
+    enabled: &[SiteSummary],
+    new_sites: &[SiteSummary],
+) -> Vec<Conflict> {
+    let mut server_names: HashMap<(String, String), String> = HashMap::new();
+    let mut default_servers: HashMap<String, String> = HashMap::new();
+    let mut conflicts: Vec<Conflict> = vec![];
+
+    let is_new = |site: &SiteSummary| new_sites.iter().any(|x| x.name == site.name);
+
+    for site in enabled.iter().filter(|x| !is_new(x)) {
+        for server in &site.servers {
+            for listen in &server.listen {
+                let key = listen_key(listen);
+
+                for server_name in &server.server_names {
+                    server_names
+                        .entry((key.clone(), server_name.to_lowercase()))
+                        .or_insert_with(|| site.name.clone());
+                }
+
+                if listen.default_server {
+                    default_servers
+                        .entry(key)
+                        .or_insert_with(|| site.name.clone());
+                }
+            }
+        }
+    }
+
+    for site in new_sites {
+        for server in &site.servers {
+            for listen in &server.listen {
+                let key = listen_key(listen);
+
+                for server_name in &server.server_names {
+                    let entry = server_names
+                        .entry((key.clone(), server_name.to_lowercase()))
+                        .or_insert_with(|| site.name.clone());
+
+                    if *entry != site.name {
+                        conflicts.push(Conflict::ServerName {
+                            listen: key.clone(),
+                            server_name: server_name.clone(),
+                            sites: (site.name.clone(), entry.clone()),
+                        });
+                    }
+                }
+
+                if listen.default_server {
+                    let entry = default_servers
+                        .entry(key.clone())
+                        .or_insert_with(|| site.name.clone());
+
+                    if *entry != site.name {
+                        conflicts.push(Conflict::DefaultServer {
+                            listen: key,
+                            sites: (site.name.clone(), entry.clone()),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    conflicts
+}
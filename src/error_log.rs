@@ -0,0 +1,401 @@
+use crate::access_log::AccessEntry;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use strum::{Display, EnumIter, EnumString};
+
+const TIME_FORMAT: &str = "%Y/%m/%d %H:%M:%S";
+/// Raw lines kept per group for the pager.
+pub const SAMPLE_LINES: usize = 1000;
+
+/// Severity of an error log entry, from least to most severe.
+#[derive(
+    Debug,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    EnumString,
+    Serialize,
+)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    Info,
+    Notice,
+    Warn,
+    Error,
+    Crit,
+    Alert,
+    Emerg,
+}
+
+/// One entry of an error log, e.g.
+/// `2026/10/18 09:12:44 [error] 812#812: *41 open() "/srv/www/favicon.ico" failed
+/// (2: No such file or directory), client: 10.0.0.7, server: example.com,
+/// request: "GET /favicon.ico HTTP/1.1", host: "example.com"`
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEntry {
+    /// Local time of the server, nginx doesn't log the offset.
+    pub time: NaiveDateTime,
+    pub level: Level,
+    pub pid: u32,
+    pub tid: u64,
+    /// `*41`, only logged while handling a connection.
+    pub connection: Option<u64>,
+    /// The message without the context nginx appends to it.
+    pub message: String,
+    pub client: Option<String>,
+    pub server: Option<String>,
+    pub request: Option<String>,
+    pub upstream: Option<String>,
+    pub host: Option<String>,
+    pub referrer: Option<String>,
+}
+
+/// Entries with the same level and message.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorGroup {
+    pub level: Level,
+    pub message: String,
+    pub count: u64,
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+    /// `server` of the entries, the sites the message was logged for.
+    pub servers: BTreeSet<String>,
+    /// Raw lines of the newest `SAMPLE_LINES` entries, newest first.
+    #[serde(skip)]
+    pub lines: Vec<(NaiveDateTime, String)>,
+}
+
+impl ErrorEntry {
+    /// `None` when `line` isn't an error log entry.
+    pub fn parse(line: &str) -> Option<Self> {
+        let time = NaiveDateTime::parse_from_str(line.get(..19)?, TIME_FORMAT).ok()?;
+
+        let rest = line[19..].strip_prefix(" [")?;
+        let (level, rest) = rest.split_once("] ")?;
+        let level: Level = level.parse().ok()?;
+
+        let (process, rest) = rest.split_once(": ")?;
+        let (pid, tid) = process.split_once('#')?;
+
+        let (connection, rest) = match rest.strip_prefix('*') {
+            Some(rest) => {
+                let (connection, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                (Some(connection.parse().ok()?), rest)
+            }
+            None => (None, rest),
+        };
+
+        let (message, context) = split_context(rest);
+
+        let mut entry = Self {
+            time,
+            level,
+            pid: pid.parse().ok()?,
+            tid: tid.parse().ok()?,
+            connection,
+            message: message.to_string(),
+            client: None,
+            server: None,
+            request: None,
+            upstream: None,
+            host: None,
+            referrer: None,
+        };
+
+        for (key, value) in context_fields(context) {
+            let field = match key {
+                "client" => &mut entry.client,
+                "server" => &mut entry.server,
+                "request" => &mut entry.request,
+                "upstream" => &mut entry.upstream,
+                "host" => &mut entry.host,
+                "referrer" => &mut entry.referrer,
+                _ => continue,
+            };
+
+            *field = Some(value.to_string());
+        }
+
+        Some(entry)
+    }
+
+    /// The request the entry was logged for, so access log filters on `ip`, `path`,
+    /// `method` and `host` apply to it.
+    pub fn access_entry(&self) -> AccessEntry {
+        let mut entry = AccessEntry {
+            remote_addr: self.client.as_ref().and_then(|x| x.parse().ok()),
+            host: self.host.clone().or_else(|| self.server.clone()),
+            referer: self.referrer.clone(),
+            ..Default::default()
+        };
+
+        // `GET /index.html HTTP/1.1`
+        if let Some(request) = &self.request {
+            let mut parts = request.splitn(3, ' ');
+
+            entry.method = parts.next().map(String::from);
+            entry.path = parts.next().map(String::from);
+            entry.protocol = parts.next().map(String::from);
+        }
+
+        entry
+    }
+}
+
+/// Splits off the `, client: ..., server: ...` context nginx appends to messages of
+/// requests.
+fn split_context(message: &str) -> (&str, &str) {
+    [", client: ", ", server: "]
+        .iter()
+        .find_map(|x| message.find(x))
+        .map_or((message, ""), |index| {
+            (&message[..index], &message[index + 2..])
+        })
+}
+
+/// `client: 10.0.0.7, request: "GET / HTTP/1.1"` -> `[("client", "10.0.0.7"), ...]`
+fn context_fields(mut context: &str) -> Vec<(&str, &str)> {
+    let mut fields: Vec<(&str, &str)> = vec![];
+
+    while let Some((key, rest)) = context.split_once(": ") {
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => match quoted.find("\", ") {
+                Some(end) => (&quoted[..end], &quoted[end + 3..]),
+                None => (quoted.strip_suffix('"').unwrap_or(quoted), ""),
+            },
+            None => rest.split_once(", ").unwrap_or((rest, "")),
+        };
+
+        fields.push((key, value));
+        context = rest;
+    }
+
+    fields
+}
+
+/// Groups entries by level and message as they are read, keeping the newest
+/// `SAMPLE_LINES` raw lines of each group so large logs aren't held in memory.
+#[derive(Debug, Default)]
+pub struct ErrorGroups {
+    groups: HashMap<(Level, String), ErrorGroup>,
+}
+
+impl ErrorGroups {
+    pub fn add(
+        &mut self,
+        entry: ErrorEntry,
+        line: String,
+    ) {
+        let group = self
+            .groups
+            .entry((entry.level, entry.message.clone()))
+            .or_insert_with(|| ErrorGroup {
+                level: entry.level,
+                message: entry.message,
+                count: 0,
+                first: entry.time,
+                last: entry.time,
+                servers: BTreeSet::new(),
+                lines: vec![],
+            });
+
+        group.count += 1;
+        group.first = group.first.min(entry.time);
+        group.last = group.last.max(entry.time);
+        group.servers.extend(entry.server);
+        group.lines.push((entry.time, line));
+
+        // Trimmed in bulk, files aren't read in time order.
+        if group.lines.len() >= SAMPLE_LINES * 2 {
+            keep_newest(&mut group.lines);
+        }
+    }
+
+    /// The groups, most frequent first.
+    pub fn finish(self) -> Vec<ErrorGroup> {
+        let mut groups: Vec<ErrorGroup> = self.groups.into_values().collect();
+
+        for group in &mut groups {
+            keep_newest(&mut group.lines);
+        }
+
+        groups.sort_by(|a, b| b.count.cmp(&a.count).then(b.last.cmp(&a.last)));
+
+        groups
+    }
+}
+
+fn keep_newest(lines: &mut Vec<(NaiveDateTime, String)>) {
+    lines.sort_by_key(|x| Reverse(x.0));
+    lines.truncate(SAMPLE_LINES);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REQUEST_LINE: &str = r#"2026/10/18 09:12:44 [error] 812#812: *41 open() "/srv/www/favicon.ico" failed (2: No such file or directory), client: 10.0.0.7, server: example.com, request: "GET /favicon.ico HTTP/1.1", host: "example.com", referrer: "https://example.com/""#;
+
+    fn entry(line: &str) -> ErrorEntry {
+        ErrorEntry::parse(line).unwrap()
+    }
+
+    #[test]
+    fn parses_request_entries() {
+        let entry = entry(REQUEST_LINE);
+
+        assert_eq!(entry.time.to_string(), "2026-10-18 09:12:44");
+        assert_eq!(entry.level, Level::Error);
+        assert_eq!((entry.pid, entry.tid), (812, 812));
+        assert_eq!(entry.connection, Some(41));
+        assert_eq!(
+            entry.message,
+            r#"open() "/srv/www/favicon.ico" failed (2: No such file or directory)"#
+        );
+        assert_eq!(entry.client.as_deref(), Some("10.0.0.7"));
+        assert_eq!(entry.server.as_deref(), Some("example.com"));
+        assert_eq!(entry.request.as_deref(), Some("GET /favicon.ico HTTP/1.1"));
+        assert_eq!(entry.upstream, None);
+        assert_eq!(entry.host.as_deref(), Some("example.com"));
+        assert_eq!(entry.referrer.as_deref(), Some("https://example.com/"));
+
+        let access = entry.access_entry();
+
+        assert_eq!(access.remote_addr, Some("10.0.0.7".parse().unwrap()));
+        assert_eq!(access.method.as_deref(), Some("GET"));
+        assert_eq!(access.path.as_deref(), Some("/favicon.ico"));
+        assert_eq!(access.protocol.as_deref(), Some("HTTP/1.1"));
+    }
+
+    #[test]
+    fn parses_entries_without_context() {
+        let entry = entry("2026/10/18 09:00:01 [notice] 1#1: signal process started");
+
+        assert_eq!(entry.level, Level::Notice);
+        assert_eq!(entry.connection, None);
+        assert_eq!(entry.message, "signal process started");
+        assert_eq!(entry.client, None);
+        assert_eq!(entry.server, None);
+    }
+
+    #[test]
+    fn parses_upstream_entries() {
+        let entry = entry(
+            r#"2026/10/18 09:30:00 [error] 812#812: *7 upstream timed out (110: Connection timed out) while reading response header from upstream, client: 10.0.0.9, server: api.example.com, request: "GET /api HTTP/1.1", upstream: "http://127.0.0.1:3000/api", host: "api.example.com""#,
+        );
+
+        assert_eq!(
+            entry.message,
+            "upstream timed out (110: Connection timed out) while reading response header from upstream"
+        );
+        assert_eq!(entry.upstream.as_deref(), Some("http://127.0.0.1:3000/api"));
+        assert_eq!(entry.host.as_deref(), Some("api.example.com"));
+    }
+
+    #[test]
+    fn skips_lines_that_are_not_entries() {
+        for line in [
+            "",
+            "nginx: [emerg] unknown directive \"foo\"",
+            "2026/10/18 09:12:44 [loud] 812#812: message",
+            "2026/10/18 09:12:44 [error] 812: message",
+            "2026/10/18 09:12:44 [error] 812#812: *x message",
+            "18/Oct/2026:09:12:44 [error] 812#812: message",
+            "2026/10/18 09:12:4€ [error] 812#812: message",
+        ] {
+            assert!(ErrorEntry::parse(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn splits_off_the_context() {
+        assert_eq!(
+            split_context("limiting requests, excess: 0.5, client: 10.0.0.1, server: a"),
+            (
+                "limiting requests, excess: 0.5",
+                "client: 10.0.0.1, server: a"
+            )
+        );
+        assert_eq!(
+            split_context("no servers are inside upstream, server: a"),
+            ("no servers are inside upstream", "server: a")
+        );
+        assert_eq!(split_context("worker exited"), ("worker exited", ""));
+    }
+
+    #[test]
+    fn groups_by_level_and_message() {
+        let mut groups = ErrorGroups::default();
+        let lines = [
+            REQUEST_LINE.to_string(),
+            REQUEST_LINE
+                .replace("09:12:44", "08:00:00")
+                .replace("example.com", "other.com"),
+            REQUEST_LINE.replace("[error]", "[warn]"),
+            "2026/10/18 10:00:00 [notice] 1#1: signal process started".to_string(),
+            "2026/10/18 11:00:00 [notice] 1#1: signal process started".to_string(),
+            "2026/10/18 12:00:00 [notice] 1#1: signal process started".to_string(),
+        ];
+
+        for line in lines {
+            groups.add(entry(&line), line);
+        }
+
+        let groups = groups.finish();
+        let summary: Vec<(Level, u64)> = groups.iter().map(|x| (x.level, x.count)).collect();
+
+        assert_eq!(
+            summary,
+            [(Level::Notice, 3), (Level::Error, 2), (Level::Warn, 1)]
+        );
+
+        let error = &groups[1];
+
+        assert_eq!(error.first.to_string(), "2026-10-18 08:00:00");
+        assert_eq!(error.last.to_string(), "2026-10-18 09:12:44");
+        assert_eq!(Vec::from_iter(&error.servers), ["example.com", "other.com"]);
+        assert_eq!(error.lines[0].1, REQUEST_LINE);
+    }
+
+    #[test]
+    fn keeps_the_newest_sample_lines() {
+        let mut groups = ErrorGroups::default();
+        let count = SAMPLE_LINES * 3 + 1;
+
+        // Out of time order, like lines of several rotated logs.
+        for i in (0..count).map(|x| (x * 7) % count) {
+            let line = format!(
+                "2026/10/18 {:02}:{:02}:{:02} [error] 1#1: failed",
+                i / 3600,
+                i / 60 % 60,
+                i % 60
+            );
+
+            groups.add(entry(&line), line);
+        }
+
+        let groups = groups.finish();
+        let group = &groups[0];
+
+        assert_eq!(group.count, count as u64);
+        assert_eq!(group.first.to_string(), "2026-10-18 00:00:00");
+        assert_eq!(group.lines.len(), SAMPLE_LINES);
+        assert_eq!(group.lines[0].0, group.last);
+        assert!(group.lines.windows(2).all(|x| x[0].0 > x[1].0));
+        // Seconds 2001 to 3000 are the newest.
+        assert_eq!(
+            group.lines.last().unwrap().0.to_string(),
+            "2026-10-18 00:33:21"
+        );
+    }
+}
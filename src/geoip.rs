@@ -0,0 +1,114 @@
+use crate::access_log::LogFormat;
+use crate::config::CONFIG;
+use anyhow::{Context, Result};
+use maxminddb::{geoip2, Reader};
+use serde::Serialize;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Looks up client IPs in the local MaxMind databases of `[geoip]`.
+pub struct GeoIp {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+/// Country and network of an IP, fields are `None` when a database doesn't know it,
+/// e.g. for private addresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IpInfo {
+    /// ISO code, e.g. `DE`.
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub as_org: Option<String>,
+}
+
+impl GeoIp {
+    /// `None` when no database is configured.
+    pub fn from_config() -> Result<Option<Self>> {
+        let databases = &CONFIG.geoip;
+
+        if databases.country.is_none() && databases.asn.is_none() {
+            return Ok(None);
+        }
+
+        let open = |path: &Option<String>| {
+            path.as_ref()
+                .map(|path| {
+                    Reader::open_readfile(path)
+                        .with_context(|| format!("Couldn't open GeoIP database {path}"))
+                })
+                .transpose()
+        };
+
+        Ok(Some(Self {
+            country: open(&databases.country)?,
+            asn: open(&databases.asn)?,
+        }))
+    }
+
+    pub fn lookup(
+        &self,
+        ip: IpAddr,
+    ) -> IpInfo {
+        let mut info = IpInfo::default();
+
+        if let Some(reader) = &self.country {
+            if let Ok(country) = reader.lookup::<geoip2::Country>(ip) {
+                info.country = country
+                    .country
+                    .or(country.registered_country)
+                    .and_then(|x| x.iso_code)
+                    .map(String::from);
+            }
+        }
+
+        if let Some(reader) = &self.asn {
+            if let Ok(asn) = reader.lookup::<geoip2::Asn>(ip) {
+                info.asn = asn.autonomous_system_number;
+                info.as_org = asn.autonomous_system_organization.map(String::from);
+            }
+        }
+
+        info
+    }
+
+    /// Prefixes an access log line with the country and network of its client, e.g.
+    /// `[DE AS3320 Deutsche Telekom AG] `. Lines without a client are left as they are.
+    pub fn annotate(
+        &self,
+        format: &LogFormat,
+        line: String,
+    ) -> String {
+        match format.parse(&line).and_then(|x| x.remote_addr) {
+            Some(ip) => format!("[{}] {line}", self.lookup(ip)),
+            None => line,
+        }
+    }
+}
+
+impl IpInfo {
+    /// `AS3320 Deutsche Telekom AG`, `None` without an ASN.
+    pub fn network(&self) -> Option<String> {
+        let asn = self.asn?;
+
+        Some(match &self.as_org {
+            Some(org) => format!("AS{asn} {org}"),
+            None => format!("AS{asn}"),
+        })
+    }
+}
+
+/// `DE AS3320 Deutsche Telekom AG`, `-` for what isn't known.
+impl fmt::Display for IpInfo {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}", self.country.as_deref().unwrap_or("-"))?;
+
+        match self.network() {
+            Some(network) => write!(f, " {network}"),
+            None => Ok(()),
+        }
+    }
+}
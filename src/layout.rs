@@ -0,0 +1,145 @@
+use crate::config::CONFIG;
+use crate::transaction::SitesTransaction;
+use crate::utils::{rm_symlink, sym_link, walk_sites_folder};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const DISABLED_SUFFIX: &str = ".disabled";
+
+/// How sites are stored and enabled, selected with `layout` in `[paths]`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Files in sites_available, enabled by a symlink in sites_enabled.
+    #[default]
+    Debian,
+    /// `*.conf` files in sites_enabled (e.g. conf.d), disabled by renaming them to
+    /// `*.conf.disabled`.
+    ConfD,
+}
+
+#[derive(Debug, Clone)]
+pub struct Site {
+    /// Path relative to the sites directory, e.g. `clients/acme.conf`. Conf.d sites are
+    /// named without the `.disabled` suffix.
+    pub name: String,
+    pub file_path: String,
+    pub enabled: bool,
+}
+
+/// Directory holding the site files.
+pub fn sites_dir() -> &'static str {
+    match CONFIG.paths().layout {
+        Layout::Debian => &CONFIG.paths().sites_available,
+        Layout::ConfD => &CONFIG.paths().sites_enabled,
+    }
+}
+
+/// File name of a new, not yet enabled site.
+pub fn new_site_file_name(name: &str) -> String {
+    match CONFIG.paths().layout {
+        Layout::Debian => name.into(),
+        Layout::ConfD if name.ends_with(".conf") => format!("{name}{DISABLED_SUFFIX}"),
+        Layout::ConfD => format!("{name}.conf{DISABLED_SUFFIX}"),
+    }
+}
+
+/// Where the site file at `file_path` is included from once it is enabled, `None` when
+/// it isn't a site file.
+pub fn enabled_path(file_path: &Path) -> Option<PathBuf> {
+    match CONFIG.paths().layout {
+        Layout::Debian => {
+            let name = file_path
+                .strip_prefix(&CONFIG.paths().sites_available)
+                .ok()?;
+            Some(Path::new(&CONFIG.paths().sites_enabled).join(name))
+        }
+        Layout::ConfD => {
+            let file_path = file_path.to_str()?;
+            let enabled_path = file_path.strip_suffix(DISABLED_SUFFIX)?;
+            Some(PathBuf::from(enabled_path))
+        }
+    }
+}
+
+/// Every site of the configured layout, sorted by name.
+pub async fn discover_sites() -> Result<Vec<Site>> {
+    let paths = CONFIG.paths();
+
+    let mut sites: Vec<Site> = match paths.layout {
+        Layout::Debian => {
+            let available = walk_sites_folder(&paths.sites_available).await?;
+            let enabled = walk_sites_folder(&paths.sites_enabled).await?;
+
+            available
+                .into_values()
+                .map(|file| Site {
+                    enabled: enabled.get(&file.file_name).is_some_and(|x| x.is_symlink),
+                    name: file.file_name,
+                    file_path: file.file_path,
+                })
+                .collect()
+        }
+        Layout::ConfD => walk_sites_folder(&paths.sites_enabled)
+            .await?
+            .into_values()
+            .filter_map(|file| match file.file_name.strip_suffix(DISABLED_SUFFIX) {
+                Some(name) if name.ends_with(".conf") => Some(Site {
+                    name: name.into(),
+                    file_path: file.file_path,
+                    enabled: false,
+                }),
+                None if file.file_name.ends_with(".conf") => Some(Site {
+                    name: file.file_name,
+                    file_path: file.file_path,
+                    enabled: true,
+                }),
+                _ => None,
+            })
+            .collect(),
+    };
+
+    sites.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(sites)
+}
+
+pub async fn find_site(name: &str) -> Result<Site> {
+    discover_sites()
+        .await?
+        .into_iter()
+        .find(|x| x.name == name)
+        .ok_or_else(|| anyhow!("{name} not found in {}", sites_dir()))
+}
+
+pub async fn enable_site(
+    site: &Site,
+    transaction: &mut SitesTransaction,
+) -> Result<()> {
+    transaction.touch(&site.name);
+
+    match CONFIG.paths().layout {
+        Layout::Debian => sym_link(site.name.clone()).await,
+        Layout::ConfD => {
+            let enabled_path = enabled_path(Path::new(&site.file_path))
+                .ok_or_else(|| anyhow!("{} is not a disabled site", site.name))?;
+            transaction.rename(&site.file_path, enabled_path)
+        }
+    }
+}
+
+pub async fn disable_site(
+    site: &Site,
+    transaction: &mut SitesTransaction,
+) -> Result<()> {
+    transaction.touch(&site.name);
+
+    match CONFIG.paths().layout {
+        Layout::Debian => rm_symlink(site.name.clone()).await,
+        Layout::ConfD => {
+            let disabled_path = format!("{}{DISABLED_SUFFIX}", site.file_path);
+            transaction.rename(&site.file_path, disabled_path)
+        }
+    }
+}
@@ -0,0 +1,899 @@
+use crate::access_log::{log_format_for, AccessEntry};
+use crate::error_log::ErrorEntry;
+use crate::log_ignore::Ignore;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use ipnet::IpNet;
+use regex::Regex;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Filter over parsed access log entries, e.g. `status>=500 and path~^/api`.
+///
+/// Conditions are `field op value` joined with `and`, `or`, `not` and parentheses.
+/// Fields: `status`, `method`, `path`, `ip`, `ua`, `referer`, `host`, `time`, `bytes`,
+/// `request_time` and `$variable` for any variable of the log format.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    /// `status=5xx`, `bytes>1M`, `request_time>=0.5`
+    Number {
+        field: Field,
+        op: Op,
+        min: f64,
+        max: f64,
+    },
+    /// `method=POST`
+    Text {
+        field: Field,
+        value: String,
+        negate: bool,
+    },
+    /// `path~^/api`
+    Regex {
+        field: Field,
+        regex: Regex,
+        negate: bool,
+    },
+    /// `ip=10.0.0.0/8`
+    Network { network: IpNet, negate: bool },
+    /// `time>=-15m`, `time<2026-10-18T12:00`
+    Time { op: Op, time: DateTime<FixedOffset> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Status,
+    Method,
+    Path,
+    Ip,
+    UserAgent,
+    Referer,
+    Host,
+    Time,
+    Bytes,
+    RequestTime,
+    Variable(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Condition(String, String, String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Open => write!(f, "("),
+            Self::Close => write!(f, ")"),
+            Self::And => write!(f, "and"),
+            Self::Or => write!(f, "or"),
+            Self::Not => write!(f, "not"),
+            Self::Condition(field, op, value) => write!(f, "{field}{op}{value}"),
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, index: 0 };
+
+        let expr = parser.or()?;
+
+        if let Some(token) = parser.tokens.get(parser.index) {
+            return Err(anyhow!("Unexpected \"{token}\" in filter"));
+        }
+
+        Ok(Self { expr })
+    }
+
+    /// A single condition, e.g. `("status", "=", "4xx")`, the value isn't quoted.
+    pub fn condition(
+        field: &str,
+        op: &str,
+        value: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            expr: Expr::Condition(condition(field, op, value)?),
+        })
+    }
+
+    pub fn matches(
+        &self,
+        entry: &AccessEntry,
+    ) -> bool {
+        self.expr.matches(entry)
+    }
+
+    /// Requests to one of `server_names`, matched like nginx matches `server_name`:
+    /// exact names, `*.example.com`, `.example.com`, `www.example.*` and `~regex`.
+    /// `None` when no name can be matched, e.g. only the `_` catch-all.
+    pub fn hosts(server_names: &[String]) -> Result<Option<Self>> {
+        let patterns: Vec<String> = server_names
+            .iter()
+            .filter_map(|name| host_pattern(name))
+            .map(|pattern| format!("(?:{pattern})"))
+            .collect();
+
+        if patterns.is_empty() {
+            return Ok(None);
+        }
+
+        let pattern = format!("(?i){}", patterns.join("|"));
+        let regex = Regex::new(&pattern).with_context(|| format!("Invalid regex: {pattern}"))?;
+
+        Ok(Some(Self {
+            expr: Expr::Condition(Condition::Regex {
+                field: Field::Host,
+                regex,
+                negate: false,
+            }),
+        }))
+    }
+
+    /// Matches when both filters match.
+    pub fn and(
+        self,
+        other: Self,
+    ) -> Self {
+        Self {
+            expr: Expr::And(Box::new(self.expr), Box::new(other.expr)),
+        }
+    }
+}
+
+/// Whether to show a line of the log at `file_path`: it isn't ignored, unless `ignore`
+/// is `None`, and, with a filter, it matches in the log's format. Error log entries
+/// match on the request they were logged for.
+pub fn line_filter(
+    file_path: &Path,
+    filter: Option<&Filter>,
+    ignore: Option<&Ignore>,
+) -> impl Fn(&str) -> bool + Send + 'static {
+    let format = (filter.is_some() || ignore.is_some_and(Ignore::needs_fields))
+        .then(|| log_format_for(file_path));
+    let filter = filter.cloned();
+    let ignore = ignore.cloned();
+
+    move |line| {
+        let entry = format.as_ref().and_then(|x| {
+            x.parse(line)
+                .or_else(|| ErrorEntry::parse(line).map(|x| x.access_entry()))
+        });
+
+        ignore
+            .as_ref()
+            .is_none_or(|x| !x.matches(line, entry.as_ref()))
+            && filter
+                .as_ref()
+                .is_none_or(|x| entry.as_ref().is_some_and(|entry| x.matches(entry)))
+    }
+}
+
+/// Regex for the hosts a `server_name` matches, `$http_host` may include the port.
+fn host_pattern(name: &str) -> Option<String> {
+    const PORT: &str = r"(?::\d+)?";
+
+    if let Some(regex) = name.strip_prefix('~') {
+        return Some(regex.to_string());
+    }
+
+    if name.is_empty() || name == "_" {
+        return None;
+    }
+
+    let pattern = if let Some(domain) = name.strip_prefix("*.") {
+        format!(r"^.+\.{}{PORT}$", regex::escape(domain))
+    } else if let Some(domain) = name.strip_prefix('.') {
+        format!(r"^(?:.+\.)?{}{PORT}$", regex::escape(domain))
+    } else if let Some(start) = name.strip_suffix(".*") {
+        format!(r"^{}\.[^:]+{PORT}$", regex::escape(start))
+    } else {
+        format!("^{}{PORT}$", regex::escape(name))
+    };
+
+    Some(pattern)
+}
+
+impl Expr {
+    fn matches(
+        &self,
+        entry: &AccessEntry,
+    ) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(entry) && b.matches(entry),
+            Self::Or(a, b) => a.matches(entry) || b.matches(entry),
+            Self::Not(a) => !a.matches(entry),
+            Self::Condition(condition) => condition.matches(entry),
+        }
+    }
+}
+
+impl Condition {
+    /// Entries without the field never match.
+    fn matches(
+        &self,
+        entry: &AccessEntry,
+    ) -> bool {
+        match self {
+            Self::Number {
+                field,
+                op,
+                min,
+                max,
+            } => {
+                let Some(value) = number(field, entry) else {
+                    return false;
+                };
+
+                match op {
+                    Op::Eq => (*min..=*max).contains(&value),
+                    Op::Ne => !(*min..=*max).contains(&value),
+                    Op::Gt => value > *max,
+                    Op::Ge => value >= *min,
+                    Op::Lt => value < *min,
+                    Op::Le => value <= *max,
+                    Op::Match | Op::NotMatch => false,
+                }
+            }
+            Self::Text {
+                field,
+                value,
+                negate,
+            } => text(field, entry).is_some_and(|x| (x == *value) != *negate),
+            Self::Regex {
+                field,
+                regex,
+                negate,
+            } => text(field, entry).is_some_and(|x| regex.is_match(&x) != *negate),
+            Self::Network { network, negate } => entry
+                .remote_addr
+                .is_some_and(|x| network.contains(&x) != *negate),
+            Self::Time { op, time } => entry.time.is_some_and(|x| match op {
+                Op::Eq => x == *time,
+                Op::Ne => x != *time,
+                Op::Gt => x > *time,
+                Op::Ge => x >= *time,
+                Op::Lt => x < *time,
+                Op::Le => x <= *time,
+                Op::Match | Op::NotMatch => false,
+            }),
+        }
+    }
+}
+
+fn number(
+    field: &Field,
+    entry: &AccessEntry,
+) -> Option<f64> {
+    match field {
+        Field::Status => entry.status.map(f64::from),
+        Field::Bytes => entry.bytes.map(|x| x as f64),
+        Field::RequestTime => entry.request_time,
+        _ => None,
+    }
+}
+
+fn text(
+    field: &Field,
+    entry: &AccessEntry,
+) -> Option<String> {
+    match field {
+        Field::Status => entry.status.map(|x| x.to_string()),
+        Field::Method => entry.method.clone(),
+        Field::Path => entry.path.clone(),
+        Field::Ip => entry.remote_addr.map(|x| x.to_string()),
+        Field::UserAgent => entry.user_agent.clone(),
+        Field::Referer => entry.referer.clone(),
+        Field::Host => entry.host.clone(),
+        Field::Time => entry.time.map(|x| x.to_rfc3339()),
+        Field::Bytes => entry.bytes.map(|x| x.to_string()),
+        Field::RequestTime => entry.request_time.map(|x| x.to_string()),
+        Field::Variable(name) => entry.fields.get(name).cloned(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    fn next_if(
+        &mut self,
+        token: &Token,
+    ) -> bool {
+        let is_next = self.tokens.get(self.index) == Some(token);
+
+        if is_next {
+            self.index += 1;
+        }
+
+        is_next
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+
+        while self.next_if(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.not()?;
+
+        while self.next_if(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.next_if(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+
+        if self.next_if(&Token::Open) {
+            let expr = self.or()?;
+
+            if !self.next_if(&Token::Close) {
+                return Err(anyhow!("Missing ) in filter"));
+            }
+
+            return Ok(expr);
+        }
+
+        match self.tokens.get(self.index).cloned() {
+            Some(Token::Condition(field, op, value)) => {
+                self.index += 1;
+                Ok(Expr::Condition(condition(&field, &op, &value)?))
+            }
+            Some(token) => Err(anyhow!("Unexpected \"{token}\" in filter")),
+            None => Err(anyhow!("Filter ends too early")),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens: Vec<Token> = vec![];
+    let chars: Vec<char> = source.chars().collect();
+    let mut index = 0;
+
+    let is_name = |x: char| x.is_ascii_alphanumeric() || x == '_' || x == '$';
+
+    while index < chars.len() {
+        let char = chars[index];
+
+        if char.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        if char == '(' || char == ')' {
+            tokens.push(if char == '(' {
+                Token::Open
+            } else {
+                Token::Close
+            });
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        while index < chars.len() && is_name(chars[index]) {
+            index += 1;
+        }
+
+        let name: String = chars[start..index].iter().collect();
+
+        if name.is_empty() {
+            return Err(anyhow!(
+                "Expected a field at {:?}",
+                &source[byte(source, start)..]
+            ));
+        }
+
+        let keyword = match name.to_lowercase().as_str() {
+            "and" => Some(Token::And),
+            "or" => Some(Token::Or),
+            "not" => Some(Token::Not),
+            _ => None,
+        };
+
+        let op_start = skip_whitespace(&chars, index);
+
+        if let Some(keyword) = keyword {
+            if !starts_with_op(&chars[op_start..]) {
+                tokens.push(keyword);
+                continue;
+            }
+        }
+
+        index = op_start;
+
+        let op = ["==", ">=", "<=", "!=", "!~", "=", "~", ">", "<"]
+            .into_iter()
+            .find(|op| chars[index..].starts_with(&op.chars().collect::<Vec<_>>()))
+            .with_context(|| format!("Expected an operator after {name}"))?;
+
+        index = skip_whitespace(&chars, index + op.len());
+
+        let value = if chars.get(index) == Some(&'"') {
+            quoted(&chars, &mut index)?
+        } else {
+            bare(&chars, &mut index)
+        };
+
+        if value.is_empty() {
+            return Err(anyhow!("Expected a value after {name}{op}"));
+        }
+
+        tokens.push(Token::Condition(name, op.into(), value));
+    }
+
+    Ok(tokens)
+}
+
+fn byte(
+    source: &str,
+    char_index: usize,
+) -> usize {
+    source
+        .char_indices()
+        .nth(char_index)
+        .map_or(source.len(), |(x, _)| x)
+}
+
+fn skip_whitespace(
+    chars: &[char],
+    mut index: usize,
+) -> usize {
+    while index < chars.len() && chars[index].is_whitespace() {
+        index += 1;
+    }
+
+    index
+}
+
+fn starts_with_op(chars: &[char]) -> bool {
+    matches!(chars.first(), Some('=' | '!' | '~' | '>' | '<'))
+}
+
+/// `"Mozilla/5.0 (X11)"`, `\"` escapes a quote.
+fn quoted(
+    chars: &[char],
+    index: &mut usize,
+) -> Result<String> {
+    let mut value = String::new();
+    *index += 1;
+
+    while let Some(char) = chars.get(*index) {
+        *index += 1;
+
+        match char {
+            '"' => return Ok(value),
+            '\\' if chars.get(*index) == Some(&'"') => {
+                value.push('"');
+                *index += 1;
+            }
+            _ => value.push(*char),
+        }
+    }
+
+    Err(anyhow!("Unclosed \" in filter"))
+}
+
+/// Runs to the next space. A `)` that doesn't close a `(` of the value ends it too,
+/// so `(path~^/(a|b))` works.
+fn bare(
+    chars: &[char],
+    index: &mut usize,
+) -> String {
+    let mut value = String::new();
+    let mut depth = 0;
+
+    while let Some(char) = chars.get(*index) {
+        match char {
+            x if x.is_whitespace() => break,
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            _ => {}
+        }
+
+        value.push(*char);
+        *index += 1;
+    }
+
+    value
+}
+
+fn condition(
+    field: &str,
+    op: &str,
+    value: &str,
+) -> Result<Condition> {
+    let field = match field.to_lowercase().as_str() {
+        "status" => Field::Status,
+        "method" => Field::Method,
+        "path" | "uri" => Field::Path,
+        "ip" | "client" => Field::Ip,
+        "ua" | "agent" | "user_agent" => Field::UserAgent,
+        "referer" | "referrer" => Field::Referer,
+        "host" => Field::Host,
+        "time" => Field::Time,
+        "bytes" | "size" => Field::Bytes,
+        "request_time" | "rt" => Field::RequestTime,
+        _ => match field.strip_prefix('$') {
+            Some(name) if !name.is_empty() => Field::Variable(name.into()),
+            _ => return Err(anyhow!("Unknown filter field: {field}")),
+        },
+    };
+
+    let op = match op {
+        "=" | "==" => Op::Eq,
+        "!=" => Op::Ne,
+        ">" => Op::Gt,
+        ">=" => Op::Ge,
+        "<" => Op::Lt,
+        "<=" => Op::Le,
+        "~" => Op::Match,
+        _ => Op::NotMatch,
+    };
+
+    if matches!(op, Op::Match | Op::NotMatch) {
+        let regex = Regex::new(value).with_context(|| format!("Invalid regex: {value}"))?;
+
+        return Ok(Condition::Regex {
+            field,
+            regex,
+            negate: op == Op::NotMatch,
+        });
+    }
+
+    match field {
+        Field::Status | Field::Bytes | Field::RequestTime => {
+            let (min, max) = number_range(&field, value)?;
+
+            Ok(Condition::Number {
+                field,
+                op,
+                min,
+                max,
+            })
+        }
+        Field::Time => Ok(Condition::Time {
+            op,
+            time: parse_time(value)?,
+        }),
+        Field::Ip if matches!(op, Op::Eq | Op::Ne) => Ok(Condition::Network {
+            network: parse_network(value)?,
+            negate: op == Op::Ne,
+        }),
+        _ if matches!(op, Op::Eq | Op::Ne) => Ok(Condition::Text {
+            value: if field == Field::Method {
+                value.to_uppercase()
+            } else {
+                value.into()
+            },
+            field,
+            negate: op == Op::Ne,
+        }),
+        _ => Err(anyhow!("Only =, !=, ~ and !~ compare {value:?}")),
+    }
+}
+
+/// `404`, `4xx`, `400-499`, `1.5`, and for bytes `10k`, `2M` or `1G`.
+fn number_range(
+    field: &Field,
+    value: &str,
+) -> Result<(f64, f64)> {
+    if *field == Field::Status {
+        if let Some(class) = value.to_lowercase().strip_suffix("xx") {
+            let class: f64 = class
+                .parse()
+                .with_context(|| format!("Invalid status: {value}"))?;
+            return Ok((class * 100.0, class * 100.0 + 99.0));
+        }
+    }
+
+    if let Some((min, max)) = value.split_once('-') {
+        return Ok((parse_number(field, min)?, parse_number(field, max)?));
+    }
+
+    let number = parse_number(field, value)?;
+
+    Ok((number, number))
+}
+
+fn parse_number(
+    field: &Field,
+    value: &str,
+) -> Result<f64> {
+    let (number, unit) = match value.char_indices().last() {
+        Some((index, unit)) if *field == Field::Bytes && unit.is_ascii_alphabetic() => {
+            (&value[..index], unit.to_ascii_lowercase())
+        }
+        _ => (value, ' '),
+    };
+
+    let multiplier = match unit {
+        'k' => 1024.0,
+        'm' => 1024.0 * 1024.0,
+        'g' => 1024.0 * 1024.0 * 1024.0,
+        ' ' => 1.0,
+        _ => return Err(anyhow!("Unknown unit in {value}, expected k, M or G")),
+    };
+
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid number: {value}"))?;
+
+    Ok(number * multiplier)
+}
+
+fn parse_network(value: &str) -> Result<IpNet> {
+    if let Ok(network) = value.parse::<IpNet>() {
+        return Ok(network);
+    }
+
+    let ip: IpAddr = value
+        .parse()
+        .with_context(|| format!("Invalid IP or CIDR: {value}"))?;
+
+    Ok(IpNet::from(ip))
+}
+
+/// `-30s`, `-15m`, `-2h`, `-7d` before now, or a local date and time such as
+/// `2026-10-18`, `2026-10-18T12:00` or `"2026-10-18 12:00:30"`, quoted for the space.
+/// RFC 3339 keeps its offset.
+fn parse_time(value: &str) -> Result<DateTime<FixedOffset>> {
+    if let Some(ago) = value.strip_prefix('-') {
+        let split = ago.char_indices().last().map_or(0, |(index, _)| index);
+        let (amount, unit) = ago.split_at(split);
+        let amount: i64 = amount
+            .parse()
+            .with_context(|| format!("Invalid time: {value}"))?;
+
+        let duration = match unit {
+            "s" => Duration::seconds(amount),
+            "m" => Duration::minutes(amount),
+            "h" => Duration::hours(amount),
+            "d" => Duration::days(amount),
+            _ => return Err(anyhow!("Unknown unit in {value}, expected s, m, h or d")),
+        };
+
+        return Ok((Local::now() - duration).fixed_offset());
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+
+    let value = value.replace(' ', "T");
+
+    let naive = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .ok()
+                .and_then(|x| x.and_hms_opt(0, 0, 0))
+        })
+        .with_context(|| format!("Invalid time: {value}"))?;
+
+    let time = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .with_context(|| format!("Invalid local time: {value}"))?;
+
+    Ok(time.fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_log::LogFormat;
+
+    fn entry(line: &str) -> AccessEntry {
+        LogFormat::combined().parse(line).unwrap()
+    }
+
+    fn request(
+        ip: &str,
+        method: &str,
+        path: &str,
+        status: u16,
+    ) -> AccessEntry {
+        entry(&format!(
+            r#"{ip} - - [18/Oct/2026:12:00:00 +0000] "{method} {path} HTTP/1.1" {status} 512 "-" "curl/8.0""#
+        ))
+    }
+
+    fn matches(
+        filter: &str,
+        entry: &AccessEntry,
+    ) -> bool {
+        Filter::parse(filter).unwrap().matches(entry)
+    }
+
+    #[test]
+    fn matches_status_classes() {
+        assert!(matches("status=4xx", &request("10.0.0.1", "GET", "/", 404)));
+        assert!(matches("status=4xx", &request("10.0.0.1", "GET", "/", 499)));
+        assert!(!matches(
+            "status=4xx",
+            &request("10.0.0.1", "GET", "/", 500)
+        ));
+        assert!(matches(
+            "status!=4xx",
+            &request("10.0.0.1", "GET", "/", 200)
+        ));
+        assert!(matches(
+            "status>=500",
+            &request("10.0.0.1", "GET", "/", 503)
+        ));
+        assert!(!matches(
+            "status>4xx",
+            &request("10.0.0.1", "GET", "/", 450)
+        ));
+    }
+
+    #[test]
+    fn matches_networks() {
+        let entry = request("10.1.2.3", "GET", "/", 200);
+
+        assert!(matches("ip=10.0.0.0/8", &entry));
+        assert!(matches("ip=10.1.2.3", &entry));
+        assert!(!matches("ip=192.168.0.0/16", &entry));
+        assert!(matches("ip!=192.168.0.0/16", &entry));
+        assert!(matches(
+            "ip=2001:db8::/32",
+            &request("2001:db8::1", "GET", "/", 200)
+        ));
+    }
+
+    #[test]
+    fn matches_relative_times() {
+        let at = |minutes: i64| AccessEntry {
+            time: Some((Local::now() - Duration::minutes(minutes)).fixed_offset()),
+            ..Default::default()
+        };
+
+        assert!(matches("time>=-15m", &at(5)));
+        assert!(!matches("time>=-15m", &at(30)));
+        assert!(matches("time<-1h", &at(90)));
+    }
+
+    #[test]
+    fn parses_absolute_times() {
+        let time = parse_time("2026-10-18T12:00:30+02:00").unwrap();
+        assert_eq!(time.to_rfc3339(), "2026-10-18T12:00:30+02:00");
+
+        let quoted = Filter::parse(r#"time>="2026-10-18 12:00:30""#);
+        assert!(quoted.is_ok());
+
+        assert!(Filter::parse("time>=2026-10-18 12:00:30").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_time_units() {
+        assert!(Filter::parse("time>=-15é").is_err());
+        assert!(Filter::parse("time>=-é").is_err());
+        assert!(Filter::parse("time>=-15w").is_err());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let get = request("10.0.0.1", "GET", "/", 404);
+        let post = request("10.0.0.1", "POST", "/", 200);
+
+        // status=404 or (method=POST and status=500)
+        assert!(matches("status=404 or method=POST and status=500", &get));
+        assert!(!matches("status=404 or method=POST and status=500", &post));
+        assert!(matches(
+            "(status=404 or method=POST) and ip=10.0.0.1",
+            &post
+        ));
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and() {
+        let entry = request("10.0.0.1", "GET", "/api/x", 200);
+
+        assert!(matches("not method=POST and path~^/api", &entry));
+        assert!(!matches("not (method=GET and path~^/api)", &entry));
+        assert!(matches("not not method=GET", &entry));
+    }
+
+    #[test]
+    fn matches_regexes_and_quoted_values() {
+        let entry = request("10.0.0.1", "GET", "/wp-login.php", 404);
+
+        assert!(matches("path~^/wp-", &entry));
+        assert!(matches("path!~^/api", &entry));
+        assert!(matches(r#"ua="curl/8.0""#, &entry));
+        assert!(matches("bytes<1k", &entry));
+        assert!(matches("$status=404", &entry));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        for filter in [
+            "",
+            "status",
+            "status=",
+            "(status=404",
+            "status=404)",
+            "foo=1",
+        ] {
+            assert!(Filter::parse(filter).is_err(), "{filter}");
+        }
+
+        assert!(Filter::parse("path~(").is_err());
+        assert!(Filter::parse("ip=10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn entries_without_the_field_never_match() {
+        let entry = AccessEntry::default();
+
+        assert!(!matches("status=4xx", &entry));
+        assert!(!matches("status!=4xx", &entry));
+        assert!(!matches("ip!=10.0.0.0/8", &entry));
+    }
+
+    #[test]
+    fn matches_error_log_entries_on_their_request() {
+        let filter = Filter::parse("ip=127.0.0.0/8 and path~^/api").unwrap();
+        let keep = line_filter(Path::new("error.log"), Some(&filter), None);
+
+        assert!(keep(
+            r#"2026/10/18 09:12:44 [error] 812#812: *41 upstream timed out, client: 127.0.0.1, server: a.com, request: "GET /api/users HTTP/1.1", host: "a.com""#
+        ));
+        assert!(!keep(
+            r#"2026/10/18 09:12:44 [error] 812#812: *42 upstream timed out, client: 10.0.0.7, server: a.com, request: "GET /api/users HTTP/1.1", host: "a.com""#
+        ));
+        assert!(!keep(
+            "2026/10/18 09:00:01 [notice] 1#1: signal process started"
+        ));
+    }
+}
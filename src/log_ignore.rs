@@ -0,0 +1,104 @@
+use crate::access_log::AccessEntry;
+use crate::config::{IgnoreRule, CONFIG};
+use crate::log_filter::Filter;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+
+/// Lines hidden from the log viewer, stats and error logs: those containing one of
+/// `ignore_values_in_log` or matching one of the `[[ignore]]` rules of the config.
+#[derive(Debug, Clone)]
+pub struct Ignore {
+    values: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    line: Option<Regex>,
+    /// The `ip`, `path`, `user_agent` and `status` conditions, all of them.
+    fields: Option<Filter>,
+}
+
+impl Ignore {
+    pub fn from_config() -> Result<Self> {
+        let rules = CONFIG
+            .ignore
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                Rule::new(rule).with_context(|| format!("Invalid ignore rule {}", index + 1))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            values: CONFIG.ignore_values_in_log.clone(),
+            rules,
+        })
+    }
+
+    /// Whether a rule matches fields of the line, which have to be parsed for it.
+    pub fn needs_fields(&self) -> bool {
+        self.rules.iter().any(|x| x.fields.is_some())
+    }
+
+    /// `entry` is the line parsed in its log format, rules on fields never match
+    /// without it.
+    pub fn matches(
+        &self,
+        line: &str,
+        entry: Option<&AccessEntry>,
+    ) -> bool {
+        self.values.iter().any(|x| line.contains(x.as_str()))
+            || self.rules.iter().any(|x| x.matches(line, entry))
+    }
+}
+
+impl Rule {
+    fn new(rule: &IgnoreRule) -> Result<Self> {
+        let line = rule
+            .line
+            .as_deref()
+            .map(|x| Regex::new(x).with_context(|| format!("Invalid regex: {x}")))
+            .transpose()?;
+
+        let conditions = [
+            ("ip", "=", &rule.ip),
+            ("path", "~", &rule.path),
+            ("ua", "~", &rule.user_agent),
+            ("status", "=", &rule.status),
+        ];
+
+        let mut fields: Option<Filter> = None;
+
+        for (field, op, value) in conditions {
+            let Some(value) = value else {
+                continue;
+            };
+
+            let condition = Filter::condition(field, op, value)?;
+
+            fields = Some(match fields {
+                Some(fields) => fields.and(condition),
+                None => condition,
+            });
+        }
+
+        if line.is_none() && fields.is_none() {
+            return Err(anyhow!("Set one of ip, path, user_agent, status or line"));
+        }
+
+        Ok(Self { line, fields })
+    }
+
+    fn matches(
+        &self,
+        line: &str,
+        entry: Option<&AccessEntry>,
+    ) -> bool {
+        self.line.as_ref().is_none_or(|x| x.is_match(line))
+            && self
+                .fields
+                .as_ref()
+                .is_none_or(|fields| entry.is_some_and(|x| fields.matches(x)))
+    }
+}
@@ -0,0 +1,405 @@
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+const FOLLOW_READ_LIMIT: u64 = 4 * 1024 * 1024;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+pub type LogLines = Box<dyn Iterator<Item = io::Result<String>> + Send>;
+
+/// Lines of a log, newest first for plain files. Gzip files can't be read backwards,
+/// they are decoded as a stream, oldest first. For reading a log in full, `newest_first`
+/// and `oldest_first` order both.
+pub fn open_log(file_path: &Path) -> Result<LogLines> {
+    if is_gzip(file_path)? {
+        Ok(Box::new(gzip_lines(file_path)?))
+    } else {
+        Ok(Box::new(ReverseLines::open(file_path)?))
+    }
+}
+
+/// Lines of a log kept by `keep`, newest first. Gzip files have to be decoded from the
+/// start, only their newest `limit` lines are kept.
+pub fn newest_first(
+    file_path: &Path,
+    keep: impl Fn(&str) -> bool + Send + 'static,
+    limit: usize,
+) -> Result<LogLines> {
+    if !is_gzip(file_path)? {
+        let lines = ReverseLines::open(file_path)?;
+        return Ok(Box::new(
+            lines.filter(move |x| x.as_ref().map_or(true, |line| keep(line))),
+        ));
+    }
+
+    let mut tail: VecDeque<String> = VecDeque::new();
+
+    for line in gzip_lines(file_path)? {
+        let line = line?;
+
+        if !keep(&line) {
+            continue;
+        }
+
+        if tail.len() == limit {
+            tail.pop_front();
+        }
+
+        tail.push_back(line);
+    }
+
+    Ok(Box::new(tail.into_iter().rev().map(Ok)))
+}
+
+/// Lines of a log in the order nginx wrote them.
+pub fn oldest_first(file_path: &Path) -> Result<LogLines> {
+    if is_gzip(file_path)? {
+        Ok(Box::new(gzip_lines(file_path)?))
+    } else {
+        Ok(Box::new(lossy_lines(BufReader::new(File::open(
+            file_path,
+        )?))))
+    }
+}
+
+pub fn is_gzip(file_path: &Path) -> Result<bool> {
+    let mut magic = [0; 2];
+    let mut file = File::open(file_path)?;
+
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(magic == GZIP_MAGIC),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// `access.log.2.gz` or `access.log-20261018.gz` (logrotate's dateext) -> `access.log`
+pub fn rotated_from(file_path: &Path) -> &Path {
+    let path = file_path.to_str().unwrap_or_default();
+    let path = path.strip_suffix(".gz").unwrap_or(path);
+
+    match path.rfind(['.', '-']) {
+        Some(index) if is_number(&path[index + 1..]) => Path::new(&path[..index]),
+        _ => Path::new(path),
+    }
+}
+
+fn is_number(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|x| x.is_ascii_digit())
+}
+
+/// The live log of `file_path` and the logs rotated from it, newest first, e.g.
+/// `access.log`, `access.log.1` and `access.log.2.gz`.
+pub fn rotated_set(file_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = vec![];
+    let live_path = rotated_from(file_path);
+    let dir = live_path.parent().unwrap_or(Path::new("."));
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && rotated_from(&path) == live_path {
+            files.push(path);
+        }
+    }
+
+    files.sort_by(|a, b| by_age(a, b));
+
+    Ok(files)
+}
+
+/// Orders logs newest first by modification time, which rotated logs keep when they are
+/// compressed.
+pub fn by_age(
+    a: &Path,
+    b: &Path,
+) -> Ordering {
+    let modified = |x: &Path| fs::metadata(x).and_then(|x| x.modified()).ok();
+
+    modified(b).cmp(&modified(a)).then_with(|| a.cmp(b))
+}
+
+/// `files`, followed by the logs rotated from each of them when `rotated` is set.
+pub fn log_files(
+    files: &[PathBuf],
+    rotated: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in files {
+        let set = match rotated {
+            true => rotated_set(file)?,
+            false => vec![file.clone()],
+        };
+
+        for path in set {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+pub fn gzip_lines(file_path: &Path) -> Result<impl Iterator<Item = io::Result<String>>> {
+    let decoder = MultiGzDecoder::new(BufReader::new(File::open(file_path)?));
+
+    Ok(lossy_lines(BufReader::new(decoder)))
+}
+
+/// Lines of `reader`, invalid UTF-8 is replaced instead of failing the whole log.
+pub fn lossy_lines(mut reader: impl BufRead) -> impl Iterator<Item = io::Result<String>> {
+    let mut buffer: Vec<u8> = vec![];
+
+    std::iter::from_fn(move || {
+        buffer.clear();
+
+        match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(to_line(&buffer))),
+            Err(err) => Some(Err(err)),
+        }
+    })
+}
+
+fn to_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Reads a file backwards in chunks, yielding its lines last to first.
+pub struct ReverseLines {
+    file: File,
+    /// Start of the part of the file that hasn't been read yet.
+    position: u64,
+    /// Beginning of the first line of the last chunk, it may continue in the next one.
+    partial: Vec<u8>,
+    /// Complete lines of the last chunk, in file order.
+    lines: Vec<String>,
+}
+
+impl ReverseLines {
+    pub fn open(file_path: &Path) -> io::Result<Self> {
+        let mut file = File::open(file_path)?;
+        let mut position = file.metadata()?.len();
+
+        // A trailing newline ends the last line, it doesn't start an empty one.
+        if position > 0 {
+            let mut last = [0; 1];
+            file.seek(SeekFrom::Start(position - 1))?;
+            file.read_exact(&mut last)?;
+
+            if last[0] == b'\n' {
+                position -= 1;
+            }
+        }
+
+        Ok(Self {
+            file,
+            position,
+            partial: vec![],
+            lines: vec![],
+        })
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let size = CHUNK_SIZE.min(self.position);
+        self.position -= size;
+
+        let mut chunk = vec![0; size as usize];
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.file.read_exact(&mut chunk)?;
+        chunk.append(&mut self.partial);
+
+        let mut parts = chunk.split(|x| *x == b'\n');
+
+        if self.position > 0 {
+            self.partial = parts.next().unwrap_or_default().to_vec();
+        }
+
+        self.lines = parts.map(to_line).collect();
+
+        Ok(())
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.lines.is_empty() && self.position > 0 {
+            if let Err(err) = self.read_chunk() {
+                self.position = 0;
+                return Some(Err(err));
+            }
+        }
+
+        self.lines.pop().map(Ok)
+    }
+}
+
+/// Reads the lines appended to a log, like `tail -F`. The log is reopened from the start
+/// when it is replaced (logrotate) or truncated.
+pub struct LogFollower {
+    file_path: PathBuf,
+    file: Option<File>,
+    inode: u64,
+    position: u64,
+    /// Byte before `position`, to notice a truncated log that has grown past it again.
+    last_byte: Option<u8>,
+    /// Last line, until its newline is written.
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Starts at the current end of the log.
+    pub fn open(file_path: &Path) -> Self {
+        let mut follower = Self {
+            file_path: file_path.to_path_buf(),
+            file: None,
+            inode: 0,
+            position: 0,
+            last_byte: None,
+            partial: vec![],
+        };
+
+        if let Ok(file) = File::open(file_path) {
+            if let Ok(metadata) = file.metadata() {
+                follower.inode = metadata.ino();
+                follower.position = metadata.len();
+                follower.file = Some(file);
+                follower.last_byte = follower.byte_before_position().ok().flatten();
+            }
+        }
+
+        follower
+    }
+
+    /// Complete lines written since the last call.
+    pub fn read_new(&mut self) -> io::Result<Vec<String>> {
+        let Ok(metadata) = fs::metadata(&self.file_path) else {
+            // Rotated away, the rest of the old file is still readable.
+            return self.read_appended();
+        };
+
+        if self.file.is_none() || metadata.ino() != self.inode {
+            let mut lines = self.read_appended()?;
+
+            self.file = Some(File::open(&self.file_path)?);
+            self.inode = metadata.ino();
+            self.rewind();
+            lines.append(&mut self.read_appended()?);
+
+            return Ok(lines);
+        }
+
+        if metadata.len() < self.position || self.byte_before_position()? != self.last_byte {
+            self.rewind();
+        }
+
+        self.read_appended()
+    }
+
+    fn rewind(&mut self) {
+        self.position = 0;
+        self.last_byte = None;
+        self.partial.clear();
+    }
+
+    fn byte_before_position(&mut self) -> io::Result<Option<u8>> {
+        let (Some(file), 1..) = (&mut self.file, self.position) else {
+            return Ok(None);
+        };
+
+        let mut byte = [0; 1];
+        file.seek(SeekFrom::Start(self.position - 1))?;
+
+        match file.read_exact(&mut byte) {
+            Ok(()) => Ok(Some(byte[0])),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_appended(&mut self) -> io::Result<Vec<String>> {
+        let Some(file) = &mut self.file else {
+            return Ok(vec![]);
+        };
+
+        // The rest is read by the next call when more than this was written.
+        let mut appended: Vec<u8> = vec![];
+        file.seek(SeekFrom::Start(self.position))?;
+        self.position += file.take(FOLLOW_READ_LIMIT).read_to_end(&mut appended)? as u64;
+
+        if let Some(last) = appended.last() {
+            self.last_byte = Some(*last);
+        }
+
+        self.partial.append(&mut appended);
+
+        let Some(end) = self.partial.iter().rposition(|x| *x == b'\n') else {
+            return Ok(vec![]);
+        };
+
+        let rest = self.partial.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        lossy_lines(complete.as_slice()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_live_log_of_rotated_logs() {
+        for (rotated, live) in [
+            ("/var/log/nginx/access.log", "/var/log/nginx/access.log"),
+            ("/var/log/nginx/access.log.1", "/var/log/nginx/access.log"),
+            (
+                "/var/log/nginx/access.log.2.gz",
+                "/var/log/nginx/access.log",
+            ),
+            (
+                "/var/log/nginx/access.log-20261018",
+                "/var/log/nginx/access.log",
+            ),
+            (
+                "/var/log/nginx/access.log-20261018.gz",
+                "/var/log/nginx/access.log",
+            ),
+            (
+                "/var/log/nginx/example.com.access.log.3.gz",
+                "/var/log/nginx/example.com.access.log",
+            ),
+            ("/var/log/nginx/access.log.gz", "/var/log/nginx/access.log"),
+        ] {
+            assert_eq!(
+                rotated_from(Path::new(rotated)),
+                Path::new(live),
+                "{rotated}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_names_ending_in_words() {
+        for path in [
+            "/var/log/nginx/access.log",
+            "/var/log/nginx/api-v2.log",
+            "error-log",
+        ] {
+            assert_eq!(rotated_from(Path::new(path)), Path::new(path));
+        }
+    }
+}
@@ -1,30 +1,68 @@
 #[macro_use]
 extern crate log;
 
+mod access_log;
+mod cli;
 mod config;
+mod conflicts;
+mod error_log;
+mod geoip;
+mod layout;
+mod log_filter;
+mod log_ignore;
+mod log_reader;
 mod ng_default;
 mod ng_disable_site;
+mod ng_doctor;
 mod ng_edit_site;
 mod ng_enable_site;
+mod ng_error_log;
+mod ng_export;
+mod ng_list_sites;
+mod ng_parse;
+mod ng_profile;
+mod ng_scanners;
 mod ng_select;
+mod ng_stats;
 mod ng_test_reload;
 mod ng_view_logs;
 mod ng_view_site;
+mod pager;
+mod parser;
+mod service;
+mod site;
+mod site_logs;
+mod staging;
+mod template;
+mod transaction;
 mod utils;
 
 use anyhow::Result;
-use ng_default::ng_default;
-use ng_disable_site::ng_disable_site;
-use ng_edit_site::ng_edit_site;
-use ng_enable_site::ng_enable_site;
+use clap::Parser;
+use cli::{Cli, NgCommand};
+use config::set_profile;
+use ng_default::{create_defaults, ng_default};
+use ng_disable_site::{disable_sites, ng_disable_site};
+use ng_doctor::ng_doctor;
+use ng_edit_site::{edit_site, ng_edit_site};
+use ng_enable_site::{enable_sites, ng_enable_site, OnConflict};
+use ng_error_log::{error_log, ng_error_log};
+use ng_export::{export, ng_export};
+use ng_list_sites::ng_list_sites;
+use ng_parse::ng_parse;
+use ng_profile::ng_profile;
+use ng_scanners::{ng_scanners, scanners};
 use ng_select::{ng_select, NgSelect};
-use ng_view_logs::ng_view_logs;
-use ng_view_site::ng_view_site;
+use ng_stats::{ng_stats, stats};
+use ng_view_logs::{ng_follow_logs, ng_view_logs, view_logs};
+use ng_view_site::{ng_view_site, view_site};
 use std::process::{self, exit};
-use utils::{init_logger, is_root, reload_nginx, test_nginx};
+use utils::{init_logger, is_root, reload_nginx, restart_nginx, test_nginx};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     init_logger();
 
     if !is_root() {
@@ -32,23 +70,100 @@ async fn main() -> Result<()> {
         exit(1);
     }
 
+    set_profile(cli.profile)?;
+
+    if let Some(command) = cli.command {
+        return run_command(command).await;
+    }
+
     loop {
         run_ngsite().await?;
     }
 }
 
+async fn run_command(command: NgCommand) -> Result<()> {
+    match command {
+        NgCommand::Defaults { names, vars } => {
+            create_defaults(names, vars.into_iter().collect()).await
+        }
+        NgCommand::Enable { sites, force } => {
+            let on_conflict = match force {
+                true => OnConflict::Force,
+                false => OnConflict::Refuse,
+            };
+
+            enable_sites(sites, on_conflict).await
+        }
+        NgCommand::Disable { sites } => disable_sites(sites).await,
+        NgCommand::List { json } => ng_list_sites(json).await,
+        NgCommand::View { site } => view_site(site).await,
+        NgCommand::Edit { site } => edit_site(site).await,
+        NgCommand::Logs {
+            files,
+            site,
+            error,
+            by_host,
+            follow,
+            rotated,
+            filter,
+        } => view_logs(files, site, error, by_host, follow, rotated, filter).await,
+        NgCommand::Stats {
+            files,
+            rotated,
+            filter,
+            top,
+            json,
+        } => stats(files, rotated, filter, top, json).await,
+        NgCommand::Errors {
+            files,
+            rotated,
+            level,
+            browse,
+            json,
+        } => error_log(files, rotated, level, browse, json).await,
+        NgCommand::Export {
+            files,
+            rotated,
+            filter,
+            format,
+            output,
+        } => export(files, rotated, filter, format, output).await,
+        NgCommand::Scanners {
+            files,
+            rotated,
+            filter,
+            snippet,
+            json,
+        } => scanners(files, rotated, filter, snippet, json).await,
+        NgCommand::Doctor { fix } => ng_doctor(fix, false).await,
+        NgCommand::Parse { file, no_includes } => ng_parse(file, no_includes),
+        NgCommand::Test => test_nginx(),
+        NgCommand::Reload => reload_nginx(),
+        NgCommand::Restart => restart_nginx(),
+    }
+}
+
 async fn run_ngsite() -> Result<()> {
     let selection = ng_select();
 
     match selection {
+        NgSelect::List => ng_list_sites(false).await?,
         NgSelect::NgDefault => ng_default().await?,
         NgSelect::Enable => ng_enable_site().await?,
         NgSelect::Disable => ng_disable_site().await?,
         NgSelect::Edit => ng_edit_site().await?,
         NgSelect::ViewSite => ng_view_site().await?,
         NgSelect::ViewLog => ng_view_logs().await?,
+        NgSelect::FollowLog => ng_follow_logs().await?,
+        NgSelect::Stats => ng_stats().await?,
+        NgSelect::ErrorLog => ng_error_log().await?,
+        NgSelect::Export => ng_export().await?,
+        NgSelect::Scanners => ng_scanners().await?,
+        NgSelect::Doctor => ng_doctor(false, true).await?,
         NgSelect::Test => test_nginx()?,
         NgSelect::Reload => reload_nginx()?,
+        NgSelect::Restart => restart_nginx()?,
+        NgSelect::Profile => ng_profile()?,
         _ => process::exit(0),
     };
 
@@ -1,6 +1,15 @@
-use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
+use crate::config::CONFIG;
+use crate::layout::{new_site_file_name, sites_dir};
+use crate::template::{
+    is_file_name, load_user_templates, render, resolve_vars, used_vars, TemplateValues,
+    TemplateVar, UserTemplate,
+};
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+use std::borrow::Cow;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Once;
 use std::{collections::HashMap, path::Path};
 use strum::{Display, EnumIter, IntoEnumIterator};
 use tokio::fs::{create_dir_all, File};
@@ -28,9 +37,13 @@ enum NgDefaults {
 
 #[derive(Debug, Clone)]
 struct FileMetaData {
-    file_path: Box<PathBuf>,
-    default_file: &'static [u8],
+    name: String,
+    description: String,
+    dir: PathBuf,
+    /// May use variables, e.g. `{{domain}}`.
     file_name: String,
+    default_file: Cow<'static, [u8]>,
+    variables: Vec<TemplateVar>,
 }
 
 impl FileMetaData {
@@ -40,57 +53,129 @@ impl FileMetaData {
         default_file: &'static [u8],
     ) -> Self {
         let file_name = file_name.into();
-        let file_path = file_path.into();
-        let file_path = Path::new(&file_path).join(&file_name);
 
         Self {
+            name: file_name.clone(),
+            description: String::new(),
+            dir: PathBuf::from(file_path.into()),
             file_name,
-            file_path: Box::new(file_path),
-            default_file,
+            default_file: Cow::Borrowed(default_file),
+            variables: vec![],
         }
     }
+
+    fn from_template(template: UserTemplate) -> Self {
+        let manifest = template.manifest;
+        let dir = Path::new(&CONFIG.paths().nginx).join(&manifest.target_dir);
+
+        Self {
+            name: manifest.name,
+            description: manifest.description,
+            dir,
+            file_name: manifest.file_name,
+            default_file: Cow::Owned(template.body),
+            variables: manifest.variables,
+        }
+    }
+
+    fn with_name(
+        mut self,
+        name: impl Into<String>,
+    ) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    fn with_variables(
+        mut self,
+        variables: Vec<TemplateVar>,
+    ) -> Self {
+        self.variables = variables;
+        self
+    }
 }
 
-// static HOST_FILE: &'static [u8] = include_bytes!("./defaults/example.com");
+/// Variables shared by the site templates, `domain` also names the created file.
+fn site_variables(default_domain: &str) -> Vec<TemplateVar> {
+    let logs = &CONFIG.paths().logs;
 
-pub async fn ng_default() -> Result<()> {
-    let default_files: HashMap<NgDefaults, FileMetaData> = HashMap::from([
+    vec![
+        TemplateVar::new("domain", "Domain name", default_domain),
+        TemplateVar::new("www_domain", "www alias", "www.{{domain}}"),
+        TemplateVar::new(
+            "access_log",
+            "Access log",
+            format!("{logs}/{{{{domain}}}}.access.log"),
+        ),
+        TemplateVar::new(
+            "error_log",
+            "Error log",
+            format!("{logs}/{{{{domain}}}}.error.log"),
+        ),
+    ]
+}
+
+fn default_files() -> HashMap<NgDefaults, FileMetaData> {
+    let paths = CONFIG.paths();
+    let nginx = paths.nginx.as_str();
+    let sites_dir = sites_dir();
+    let nginxconfig_io = Path::new(nginx).join("nginxconfig.io");
+    let nginxconfig_io = nginxconfig_io.to_string_lossy();
+
+    HashMap::from([
         (
             NgDefaults::NginxConf,
-            FileMetaData::new(
-                "/etc/nginx",
-                "nginx.conf",
-                include_bytes!("./defaults/nginx.conf"),
-            ),
+            FileMetaData::new(nginx, "nginx.conf", include_bytes!("./defaults/nginx.conf")),
         ),
         (
             NgDefaults::DefaultServer,
             FileMetaData::new(
-                "/etc/nginx/sites-available",
-                "default_server",
+                sites_dir,
+                new_site_file_name("default_server"),
                 include_bytes!("./defaults/default_server"),
             ),
         ),
         (
             NgDefaults::ExampleCom,
             FileMetaData::new(
-                "/etc/nginx/sites-available",
-                "example.com",
+                sites_dir,
+                new_site_file_name("{{domain}}"),
                 include_bytes!("./defaults/example.com"),
+            )
+            .with_variables(
+                [
+                    site_variables("example.com"),
+                    vec![TemplateVar::new(
+                        "web_root",
+                        "Web root",
+                        "/var/www/{{domain}}",
+                    )],
+                ]
+                .concat(),
             ),
         ),
         (
             NgDefaults::ProxyCom,
             FileMetaData::new(
-                "/etc/nginx/sites-available",
-                "proxy.com",
+                sites_dir,
+                new_site_file_name("{{domain}}"),
                 include_bytes!("./defaults/proxy.com"),
+            )
+            .with_variables(
+                [
+                    site_variables("proxy.com"),
+                    vec![
+                        TemplateVar::new("upstream_host", "Upstream host", "127.0.0.1"),
+                        TemplateVar::new("upstream_port", "Upstream port", "3000"),
+                    ],
+                ]
+                .concat(),
             ),
         ),
         (
             NgDefaults::GeneralConf,
             FileMetaData::new(
-                "/etc/nginx/nginxconfig.io",
+                nginxconfig_io.clone(),
                 "general.conf",
                 include_bytes!("./defaults/general.conf"),
             ),
@@ -98,7 +183,7 @@ pub async fn ng_default() -> Result<()> {
         (
             NgDefaults::LetsencryptConf,
             FileMetaData::new(
-                "/etc/nginx/nginxconfig.io",
+                nginxconfig_io.clone(),
                 "letsencrypt.conf",
                 include_bytes!("./defaults/letsencrypt.conf"),
             ),
@@ -106,7 +191,7 @@ pub async fn ng_default() -> Result<()> {
         (
             NgDefaults::ProxyConf,
             FileMetaData::new(
-                "/etc/nginx/nginxconfig.io",
+                nginxconfig_io.clone(),
                 "proxy.conf",
                 include_bytes!("./defaults/proxy.conf"),
             ),
@@ -114,14 +199,68 @@ pub async fn ng_default() -> Result<()> {
         (
             NgDefaults::SecurityConf,
             FileMetaData::new(
-                "/etc/nginx/nginxconfig.io",
+                nginxconfig_io.clone(),
                 "security.conf",
                 include_bytes!("./defaults/security.conf"),
             ),
         ),
-    ]);
+    ])
+}
+
+impl fmt::Display for FileMetaData {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        if self.description.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{} - {}", self.name, self.description)
+        }
+    }
+}
+
+/// Built-in defaults followed by the user templates, a user template replaces the
+/// built-in default of the same name.
+fn catalog() -> Vec<FileMetaData> {
+    static OVERRIDES_WARNED: Once = Once::new();
+
+    let mut default_files = default_files();
+    let mut catalog: Vec<FileMetaData> = NgDefaults::iter()
+        .filter_map(|x| {
+            default_files
+                .remove(&x)
+                .map(|file| file.with_name(x.to_string()))
+        })
+        .collect();
+    let mut overrides: Vec<String> = vec![];
+
+    for template in load_user_templates() {
+        let file = FileMetaData::from_template(template);
 
-    let multi_selections: Vec<_> = NgDefaults::iter().collect();
+        match catalog.iter_mut().find(|x| x.name == file.name) {
+            Some(built_in) => {
+                overrides.push(file.name.clone());
+                *built_in = file;
+            }
+            None => catalog.push(file),
+        }
+    }
+
+    if !overrides.is_empty() {
+        OVERRIDES_WARNED.call_once(|| {
+            warn!(
+                "User template(s) {} replace the built-in defaults of the same name...",
+                overrides.join(", ")
+            )
+        });
+    }
+
+    catalog
+}
+
+pub async fn ng_default() -> Result<()> {
+    let multi_selections = catalog();
 
     let selections = MultiSelect::with_theme(&ColorfulTheme::default())
         .with_prompt("Pick one or more")
@@ -129,40 +268,100 @@ pub async fn ng_default() -> Result<()> {
         .interact()?;
 
     for selection in selections {
-        let selected = multi_selections[selection];
-        let default_file: FileMetaData = match selected {
-            NgDefaults::ExampleCom | NgDefaults::ProxyCom => {
-                let default_file = default_files.get(&selected).unwrap();
+        let default_file = &multi_selections[selection];
+
+        write_default(default_file, &TemplateValues::new(), true).await?;
+    }
+    Ok(())
+}
 
-                let domain: String = Input::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Domain name")
-                    .default(default_file.file_name.clone())
-                    .interact_text()
-                    .unwrap();
+pub async fn create_defaults(
+    names: Vec<String>,
+    values: TemplateValues,
+) -> Result<()> {
+    let catalog = catalog();
+    let mut selections: Vec<&FileMetaData> = vec![];
 
-                let dir = default_file.file_path.parent().unwrap().to_str().unwrap();
+    for name in &names {
+        let selected = catalog.iter().find(|x| x.name == *name);
 
-                FileMetaData::new(dir, domain, default_file.default_file)
+        match selected {
+            Some(selected) => selections.push(selected),
+            None => {
+                let valid: Vec<&str> = catalog.iter().map(|x| x.name.as_str()).collect();
+                return Err(anyhow!(
+                    "Unknown default {name:?}, expected one of: {}",
+                    valid.join(", ")
+                ));
             }
-            _ => default_files.get(&selected).unwrap().clone(),
-        };
+        }
+    }
 
-        let file_path = default_file.file_path;
-        let file_name = file_path.file_name().unwrap();
+    for key in values.keys() {
+        let is_used = selections
+            .iter()
+            .any(|selected| selected.variables.iter().any(|var| &var.name == key));
 
-        info!("Creating {:?}...", file_name);
+        if !is_used {
+            return Err(anyhow!("Unknown template variable: {key}"));
+        }
+    }
 
-        let file_exists = file_path.exists();
+    for selected in selections {
+        write_default(selected, &values, false).await?;
+    }
 
-        if !file_exists {
-            create_dir_all(&file_path.parent().unwrap()).await?;
-            let mut file = File::create(*file_path).await?;
+    Ok(())
+}
 
-            file.write_all(default_file.default_file).await?;
-            info!("File created...");
-        } else {
-            warn!("{:?} File already exists...", file_name);
+async fn write_default(
+    default_file: &FileMetaData,
+    preset: &TemplateValues,
+    interactive: bool,
+) -> Result<()> {
+    let mut contents = default_file.default_file.to_vec();
+    let values = resolve_vars(&default_file.variables, preset, interactive)?;
+
+    // Values in the file name must not lead out of the target directory.
+    for name in used_vars(&default_file.file_name) {
+        if let Some(value) = values.get(name) {
+            if !is_file_name(value) {
+                return Err(anyhow!(
+                    "Invalid {name} {value:?}, it names the created file"
+                ));
+            }
         }
     }
+
+    let file_name = render(&default_file.file_name, &values)
+        .with_context(|| format!("Invalid file name of {}", default_file.name))?;
+
+    if !is_file_name(&file_name) {
+        return Err(anyhow!(
+            "Invalid file name {file_name:?} of {}",
+            default_file.name
+        ));
+    }
+
+    if !default_file.variables.is_empty() {
+        contents = render(&String::from_utf8(contents)?, &values)?.into_bytes();
+    }
+
+    let file_path = default_file.dir.join(&file_name);
+
+    info!("Creating {:?}...", file_name);
+
+    let file_exists = file_path.exists();
+
+    if !file_exists {
+        create_dir_all(&file_path.parent().unwrap()).await?;
+        let mut file = File::create(&file_path).await?;
+
+        file.write_all(&contents).await?;
+        info!("File created...");
+    } else {
+        warn!("{:?} File already exists...", file_name);
+    }
+
     Ok(())
 }
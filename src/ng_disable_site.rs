@@ -1,48 +1,69 @@
-use crate::config::CONFIG;
-use crate::utils::{reload_nginx, rm_symlink, test_nginx, walk_folder, FileData};
-use anyhow::Result;
+use crate::layout::{disable_site, discover_sites, Site};
+use crate::transaction::SitesTransaction;
+use crate::utils::reload_nginx;
+use anyhow::{anyhow, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect};
 
-async fn get_site_names() -> Result<Vec<FileData>> {
-    let mut list: Vec<FileData> = vec![];
-
-    let enabled = walk_folder(&CONFIG.paths.sites_enabled).await?;
-
-    for (_, file) in enabled {
-        if file.is_symlink {
-            list.push(file)
-        }
-    }
-
-    list.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+async fn get_site_names() -> Result<Vec<Site>> {
+    let list: Vec<Site> = discover_sites()
+        .await?
+        .into_iter()
+        .filter(|x| x.enabled)
+        .collect();
 
     Ok(list)
 }
 
 pub async fn ng_disable_site() -> Result<()> {
-    let list: Vec<FileData> = get_site_names().await?;
+    let list: Vec<Site> = get_site_names().await?;
     if !list.is_empty() {
-        let multi_selections: &Vec<&String> = &list.iter().map(|x| &x.file_name).collect();
+        let multi_selections: &Vec<&String> = &list.iter().map(|x| &x.name).collect();
         let selections = MultiSelect::with_theme(&ColorfulTheme::default())
             .with_prompt("Pick site(s)")
             .items(&multi_selections[..])
             .interact()?;
 
-        if !selections.is_empty() {
-            for selection in selections {
-                let file_to_unlink = multi_selections[selection].clone();
-                if let Err(err) = rm_symlink(file_to_unlink).await {
-                    error!("Failed to symlink...");
-                    return Err(err);
-                }
-            }
-
-            test_nginx()?;
-            reload_nginx()?;
-        }
+        let sites: Vec<String> = selections
+            .into_iter()
+            .map(|selection| multi_selections[selection].clone())
+            .collect();
+
+        disable_sites(sites).await?;
     } else {
         info!("All sites are disabled...");
     }
 
     Ok(())
 }
+
+pub async fn disable_sites(sites: Vec<String>) -> Result<()> {
+    if sites.is_empty() {
+        return Ok(());
+    }
+
+    let list: Vec<Site> = get_site_names().await?;
+
+    let mut selected: Vec<&Site> = vec![];
+
+    for site in &sites {
+        match list.iter().find(|x| &x.name == site) {
+            Some(site) => selected.push(site),
+            None => return Err(anyhow!("{site} is not enabled")),
+        }
+    }
+
+    let mut transaction = SitesTransaction::begin()?;
+
+    for site in selected {
+        if let Err(err) = disable_site(site, &mut transaction).await {
+            error!("Failed to disable {}...", site.name);
+            transaction.rollback()?;
+            return Err(err);
+        }
+    }
+
+    transaction.commit()?;
+    reload_nginx()?;
+
+    Ok(())
+}
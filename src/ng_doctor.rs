@@ -0,0 +1,296 @@
+use crate::config::CONFIG;
+use crate::layout::Layout;
+use crate::transaction::SitesTransaction;
+use crate::utils::reload_nginx;
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+use std::fmt;
+use std::fs::{self, read_link, remove_file};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Something in sites-enabled that isn't a symlink to a file of sites-available.
+#[derive(Debug, Clone)]
+enum Issue {
+    /// Symlink to a file that no longer exists.
+    Dangling { name: String, target: PathBuf },
+    /// Symlink to a file outside sites-available.
+    Foreign {
+        name: String,
+        target: PathBuf,
+        available: Option<PathBuf>,
+        identical: bool,
+    },
+    /// Regular file instead of a symlink.
+    Copied {
+        name: String,
+        path: PathBuf,
+        available: Option<PathBuf>,
+        identical: bool,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Self::Dangling { name, target } => {
+                write!(f, "{name}: links to {target:?} which doesn't exist")
+            }
+            Self::Foreign {
+                name,
+                target,
+                available: Some(_),
+                identical: false,
+            } => write!(
+                f,
+                "{name}: links to {target:?} outside sites-available that differs from its sites-available file"
+            ),
+            Self::Foreign { name, target, .. } => {
+                write!(f, "{name}: links to {target:?} outside sites-available")
+            }
+            Self::Copied {
+                name,
+                available: None,
+                ..
+            } => write!(f, "{name}: is a copied file, not a symlink"),
+            Self::Copied {
+                name,
+                identical: true,
+                ..
+            } => write!(f, "{name}: is a copy of its sites-available file"),
+            Self::Copied { name, .. } => write!(
+                f,
+                "{name}: is a copied file that differs from its sites-available file"
+            ),
+        }
+    }
+}
+
+impl Issue {
+    /// What `fix` does, `None` when it has to be solved by hand.
+    fn fix_description(&self) -> Option<String> {
+        match self {
+            Self::Dangling { .. } => Some("remove the link".into()),
+            Self::Foreign {
+                available: Some(available),
+                identical: true,
+                ..
+            } => Some(format!("relink to {available:?}")),
+            Self::Foreign {
+                available: None, ..
+            } => Some("copy it into sites-available and relink".into()),
+            Self::Foreign { .. } => None,
+            Self::Copied {
+                available: None, ..
+            } => Some("move it to sites-available and link it".into()),
+            Self::Copied {
+                identical: true, ..
+            } => Some("replace it with a link to sites-available".into()),
+            Self::Copied { .. } => None,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Dangling { name, .. }
+            | Self::Foreign { name, .. }
+            | Self::Copied { name, .. } => name,
+        }
+    }
+
+    fn fix(
+        &self,
+        transaction: &mut SitesTransaction,
+    ) -> Result<()> {
+        let enabled_path = Path::new(&CONFIG.paths().sites_enabled).join(self.name());
+        let available_path = Path::new(&CONFIG.paths().sites_available).join(self.name());
+
+        transaction.touch(self.name());
+
+        match self {
+            Self::Dangling { .. } => remove_file(enabled_path)?,
+            Self::Foreign {
+                target, available, ..
+            } => {
+                if available.is_none() {
+                    create_parent(&available_path)?;
+                    fs::copy(target, &available_path)?;
+                    transaction.created_file(&available_path);
+                }
+
+                remove_file(&enabled_path)?;
+                symlink(&available_path, enabled_path)?;
+            }
+            Self::Copied {
+                path, available, ..
+            } => {
+                transaction.backup_file(path)?;
+
+                if available.is_none() {
+                    create_parent(&available_path)?;
+                    fs::copy(path, &available_path)?;
+                    transaction.created_file(&available_path);
+                }
+
+                remove_file(path)?;
+                symlink(&available_path, enabled_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    Ok(())
+}
+
+fn find_issues() -> Result<Vec<Issue>> {
+    let enabled_dir = Path::new(&CONFIG.paths().sites_enabled);
+    let available_dir = Path::new(&CONFIG.paths().sites_available);
+    let canonical_available = available_dir.canonicalize().ok();
+    let mut issues: Vec<Issue> = vec![];
+
+    if !enabled_dir.exists() {
+        return Ok(issues);
+    }
+
+    let recursive = CONFIG.paths().recursive;
+    let max_depth = if recursive { usize::MAX } else { 1 };
+
+    for entry in WalkDir::new(enabled_dir).min_depth(1).max_depth(max_depth) {
+        let entry = entry?;
+        let file_type = entry.file_type();
+        let path = entry.path().to_path_buf();
+        let name: String = path.strip_prefix(enabled_dir)?.to_string_lossy().into();
+
+        if file_type.is_dir() {
+            continue;
+        }
+
+        let available = Some(available_dir.join(&name)).filter(|x| x.is_file());
+        let identical = |content: &Path| -> Result<bool> {
+            match &available {
+                Some(available) => Ok(fs::read(available)? == fs::read(content)?),
+                None => Ok(false),
+            }
+        };
+
+        if file_type.is_symlink() {
+            let target = path.parent().unwrap_or(enabled_dir).join(read_link(&path)?);
+
+            let Ok(canonical_target) = target.canonicalize() else {
+                issues.push(Issue::Dangling { name, target });
+                continue;
+            };
+
+            let is_available = canonical_available
+                .as_ref()
+                .and_then(|x| canonical_target.strip_prefix(x).ok())
+                .is_some_and(|x| recursive || x.components().count() == 1);
+
+            if !is_available {
+                issues.push(Issue::Foreign {
+                    identical: identical(&canonical_target)?,
+                    name,
+                    target,
+                    available,
+                });
+            }
+        } else {
+            let identical = identical(&path)?;
+
+            issues.push(Issue::Copied {
+                name,
+                path,
+                available,
+                identical,
+            });
+        }
+    }
+
+    issues.sort_by(|a, b| a.name().cmp(b.name()));
+
+    Ok(issues)
+}
+
+/// Reports broken entries of sites-enabled and offers to fix them. With `fix` every
+/// fixable issue is fixed without asking.
+pub async fn ng_doctor(
+    fix: bool,
+    interactive: bool,
+) -> Result<()> {
+    if CONFIG.paths().layout != Layout::Debian {
+        info!("Doctor only checks sites-enabled of the debian layout...");
+        return Ok(());
+    }
+
+    let issues = find_issues()?;
+
+    if issues.is_empty() {
+        info!("No issues found in {}...", CONFIG.paths().sites_enabled);
+        return Ok(());
+    }
+
+    for issue in &issues {
+        match issue.fix_description() {
+            Some(fix) => warn!("{issue} (fix: {fix})"),
+            None => warn!("{issue} (fix by hand)"),
+        }
+    }
+
+    let fixable: Vec<&Issue> = issues
+        .iter()
+        .filter(|x| x.fix_description().is_some())
+        .collect();
+
+    let to_fix: Vec<&Issue> = if fix {
+        fixable
+    } else if interactive && !fixable.is_empty() {
+        let items: Vec<String> = fixable
+            .iter()
+            .map(|x| format!("{}: {}", x.name(), x.fix_description().unwrap_or_default()))
+            .collect();
+
+        let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Pick fix(es)")
+            .items(&items[..])
+            .interact()?;
+
+        selections.into_iter().map(|x| fixable[x]).collect()
+    } else {
+        vec![]
+    };
+
+    let remaining = issues.len() - to_fix.len();
+
+    if !to_fix.is_empty() {
+        let mut transaction = SitesTransaction::begin()?;
+
+        for issue in &to_fix {
+            if let Err(err) = issue.fix(&mut transaction) {
+                transaction.rollback()?;
+                return Err(err);
+            }
+
+            info!("Fixed {}...", issue.name());
+        }
+
+        transaction.commit()?;
+        reload_nginx()?;
+    }
+
+    if remaining > 0 && !interactive {
+        return Err(anyhow!("{remaining} issue(s) left in sites-enabled"));
+    }
+
+    Ok(())
+}
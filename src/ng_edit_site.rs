@@ -1,27 +1,27 @@
-use crate::config::CONFIG;
+use crate::layout::{discover_sites, find_site, Site};
 use crate::ng_test_reload::ng_test_reload;
-use crate::utils::{edit_nginx_site, walk_folder, FileData};
+use crate::staging::StagedConfig;
+use crate::transaction::SitesTransaction;
+use crate::utils::edit_text;
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Select};
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use strum::{Display, EnumIter, IntoEnumIterator};
 
-async fn get_site_names() -> Result<Vec<FileData>> {
-    let mut list: Vec<FileData> = vec![];
-
-    let available = walk_folder(&CONFIG.paths.sites_available).await?;
-
-    for (_, file) in available {
-        list.push(file)
-    }
-
-    list.sort_by(|a, b| a.file_name.cmp(&b.file_name));
-
-    Ok(list)
+#[derive(Debug, Display, PartialEq, Clone, Copy, EnumIter)]
+enum OnTestFailure {
+    #[strum(serialize = "Edit again")]
+    EditAgain,
+    #[strum(serialize = "Discard changes")]
+    Discard,
 }
 
 pub async fn ng_edit_site() -> Result<()> {
-    let list: Vec<FileData> = get_site_names().await?;
+    let list: Vec<Site> = discover_sites().await?;
     if !list.is_empty() {
-        let selections: &Vec<&String> = &list.iter().map(|x| &x.file_name).collect();
+        let selections: &Vec<&String> = &list.iter().map(|x| &x.name).collect();
         let selection = Select::with_theme(&ColorfulTheme::default())
             .with_prompt("Pick site")
             .default(0)
@@ -30,11 +30,105 @@ pub async fn ng_edit_site() -> Result<()> {
 
         let selected_site = selections[selection].clone();
 
-        edit_nginx_site(selected_site)?;
-        ng_test_reload()?;
+        edit_site(selected_site).await?;
     } else {
         info!("No sites found to edit...");
     }
 
     Ok(())
 }
+
+/// Edits a scratch copy of the site, the real file is only replaced once the copy
+/// passes `nginx -t` in a staged config tree.
+pub async fn edit_site(site: String) -> Result<()> {
+    let file_path = PathBuf::from(find_site(&site).await?.file_path);
+    let original = fs::read_to_string(&file_path)?;
+
+    let Some(modified) = edit_scratch(&site, &file_path, &original)? else {
+        return Ok(());
+    };
+
+    let mut transaction = SitesTransaction::begin()?;
+    transaction.backup_file(&file_path)?;
+    transaction.touch(&site);
+
+    fs::write(&file_path, modified)?;
+    info!("{site} updated.");
+
+    ng_test_reload(transaction)?;
+
+    Ok(())
+}
+
+/// Returns the validated content, or `None` when there is nothing to apply.
+fn edit_scratch(
+    site: &str,
+    file_path: &Path,
+    original: &str,
+) -> Result<Option<String>> {
+    let mut scratch = original.to_string();
+
+    loop {
+        let Some(modified) = edit_text(&scratch)? else {
+            if scratch == original {
+                info!("No changes made to {site}...");
+                return Ok(None);
+            }
+
+            // Closed without saving after "Edit again", the failing edits are in `scratch`.
+            if ask_on_failure("Editor closed without saving")? {
+                continue;
+            }
+
+            warn!("Changes to {site} discarded...");
+            return Ok(None);
+        };
+
+        if modified == original {
+            info!("No changes made to {site}...");
+            return Ok(None);
+        }
+
+        print_diff(site, original, &modified);
+
+        let tested = StagedConfig::new(file_path, modified.as_bytes()).and_then(|x| x.test());
+
+        let Err(err) = tested else {
+            return Ok(Some(modified));
+        };
+
+        error!("{err}");
+        scratch = modified;
+
+        if !ask_on_failure("Nginx test failed")? {
+            warn!("Changes to {site} discarded...");
+            return Ok(None);
+        }
+    }
+}
+
+/// Whether to edit the failing changes again rather than discard them.
+fn ask_on_failure(prompt: &str) -> Result<bool> {
+    let selections: Vec<_> = OnTestFailure::iter().collect();
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(0)
+        .items(&selections[..])
+        .interact()?;
+
+    Ok(selections[selection] == OnTestFailure::EditAgain)
+}
+
+fn print_diff(
+    site: &str,
+    original: &str,
+    modified: &str,
+) {
+    let diff = TextDiff::from_lines(original, modified);
+
+    println!(
+        "{}",
+        diff.unified_diff()
+            .header(&format!("a/{site}"), &format!("b/{site}"))
+    );
+}
@@ -1,50 +1,137 @@
-use crate::config::CONFIG;
-use crate::utils::{reload_nginx, sym_link, test_nginx, walk_folder, FileData};
-use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, MultiSelect};
-
-async fn get_site_names() -> Result<Vec<FileData>> {
-    let mut list: Vec<FileData> = vec![];
-    let available = walk_folder(&CONFIG.paths.sites_available).await?;
-    let enabled = walk_folder(&CONFIG.paths.sites_enabled).await?;
-
-    for (key, file) in available {
-        if let Some(enabled_file) = enabled.get(&key) {
-            if !enabled_file.is_symlink {
-                list.push(file)
-            }
-        } else {
-            list.push(file)
-        }
-    }
+use crate::conflicts::find_conflicts;
+use crate::layout::{discover_sites, enable_site, sites_dir, Site};
+use crate::site::{list_sites, SiteSummary};
+use crate::transaction::SitesTransaction;
+use crate::utils::reload_nginx;
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect};
+
+/// What to do when a site collides with an enabled one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnConflict {
+    Refuse,
+    Ask,
+    Force,
+}
 
-    list.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+async fn get_site_names() -> Result<Vec<Site>> {
+    let list: Vec<Site> = discover_sites()
+        .await?
+        .into_iter()
+        .filter(|x| !x.enabled)
+        .collect();
 
     Ok(list)
 }
 
 pub async fn ng_enable_site() -> Result<()> {
-    let list: Vec<FileData> = get_site_names().await?;
+    let list: Vec<Site> = get_site_names().await?;
     if !list.is_empty() {
-        let multi_selections: &Vec<&String> = &list.iter().map(|x| &x.file_name).collect();
+        let multi_selections: &Vec<&String> = &list.iter().map(|x| &x.name).collect();
 
         let selections = MultiSelect::with_theme(&ColorfulTheme::default())
             .with_prompt("Pick site(s)")
             .items(&multi_selections[..])
             .interact()?;
 
-        if !selections.is_empty() {
-            for selection in selections {
-                let file_to_link = multi_selections[selection].clone();
-                sym_link(file_to_link).await?;
-            }
+        let sites: Vec<String> = selections
+            .into_iter()
+            .map(|selection| multi_selections[selection].clone())
+            .collect();
 
-            test_nginx()?;
-            reload_nginx()?;
-        }
+        enable_sites(sites, OnConflict::Ask).await?;
     } else {
         info!("All sites are enabled...");
     }
 
     Ok(())
 }
+
+pub async fn enable_sites(
+    sites: Vec<String>,
+    on_conflict: OnConflict,
+) -> Result<()> {
+    if sites.is_empty() {
+        return Ok(());
+    }
+
+    let all_sites = discover_sites().await?;
+
+    for site in &sites {
+        if !all_sites.iter().any(|x| &x.name == site) {
+            return Err(anyhow!("{site} not found in {}", sites_dir()));
+        }
+    }
+
+    let list: Vec<Site> = all_sites.into_iter().filter(|x| !x.enabled).collect();
+
+    let new_sites: Vec<SiteSummary> = list
+        .iter()
+        .filter(|x| sites.contains(&x.name))
+        .map(|x| SiteSummary::parse(&x.name, &x.file_path, true))
+        .collect();
+
+    if !check_conflicts(&new_sites, on_conflict).await? {
+        return Ok(());
+    }
+
+    let mut transaction = SitesTransaction::begin()?;
+
+    for site in sites {
+        let Some(site) = list.iter().find(|x| x.name == site) else {
+            info!("{site} is already enabled...");
+            continue;
+        };
+
+        if let Err(err) = enable_site(site, &mut transaction).await {
+            transaction.rollback()?;
+            return Err(err);
+        }
+    }
+
+    transaction.commit()?;
+    reload_nginx()?;
+
+    Ok(())
+}
+
+/// Returns whether enabling `new_sites` should go ahead.
+async fn check_conflicts(
+    new_sites: &[SiteSummary],
+    on_conflict: OnConflict,
+) -> Result<bool> {
+    let enabled: Vec<SiteSummary> = list_sites()
+        .await?
+        .into_iter()
+        .filter(|x| x.enabled)
+        .collect();
+
+    let conflicts = find_conflicts(&enabled, new_sites);
+
+    if conflicts.is_empty() {
+        return Ok(true);
+    }
+
+    for conflict in &conflicts {
+        warn!("{conflict}");
+    }
+
+    match on_conflict {
+        OnConflict::Refuse => Err(anyhow!(
+            "Refusing to enable conflicting site(s), use --force to enable anyway"
+        )),
+        OnConflict::Ask => {
+            let proceed = Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enable anyway?")
+                .default(false)
+                .interact()?;
+
+            if !proceed {
+                info!("Skipping enable...");
+            }
+
+            Ok(proceed)
+        }
+        OnConflict::Force => Ok(true),
+    }
+}
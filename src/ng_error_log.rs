@@ -0,0 +1,177 @@
+use crate::error_log::{ErrorEntry, ErrorGroup, ErrorGroups, Level};
+use crate::log_ignore::Ignore;
+use crate::log_reader::{log_files, open_log};
+use crate::ng_view_logs::{find_log, pick_log_files};
+use crate::pager::cli_pager;
+use crate::utils::print_table;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use dialoguer::{theme::ColorfulTheme, FuzzySelect, Select};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use strum::IntoEnumIterator;
+
+/// Longest message shown in the group picker, the pager shows the full lines.
+const PICKER_MESSAGE_CHARS: usize = 100;
+
+/// Reads every file in full, keeping entries of at least `level`.
+fn read_groups(
+    files: &[PathBuf],
+    level: Level,
+) -> Result<Vec<ErrorGroup>> {
+    let ignore = Ignore::from_config()?;
+    let mut groups = ErrorGroups::default();
+    let mut unparsed = 0;
+
+    for file_path in files {
+        for line in open_log(file_path)? {
+            let line = line?;
+
+            let entry = ErrorEntry::parse(&line);
+
+            if ignore.matches(&line, entry.as_ref().map(|x| x.access_entry()).as_ref()) {
+                continue;
+            }
+
+            match entry {
+                Some(entry) if entry.level >= level => groups.add(entry, line),
+                Some(_) => {}
+                None => unparsed += 1,
+            }
+        }
+    }
+
+    if unparsed > 0 {
+        warn!("{unparsed} line(s) aren't error log entries and were skipped...");
+    }
+
+    Ok(groups.finish())
+}
+
+pub async fn ng_error_log() -> Result<()> {
+    let Some(files) = pick_log_files().await? else {
+        info!("No logs found...");
+        return Ok(());
+    };
+
+    let levels: Vec<Level> = Level::iter().collect();
+    let level = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Minimum severity")
+        .default(levels.iter().position(|x| *x == Level::Warn).unwrap_or(0))
+        .items(&levels[..])
+        .interact()?;
+
+    let files: Vec<PathBuf> = files.iter().map(|x| PathBuf::from(&x.file_path)).collect();
+    let groups = read_groups(&files, levels[level])?;
+
+    browse_groups(&groups).await
+}
+
+pub async fn error_log(
+    files: Vec<String>,
+    rotated: bool,
+    level: Level,
+    browse: bool,
+    json: bool,
+) -> Result<()> {
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in &files {
+        paths.push(PathBuf::from(find_log(file).await?.file_path));
+    }
+
+    let groups = read_groups(&log_files(&paths, rotated)?, level)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&groups)?);
+    } else if browse {
+        browse_groups(&groups).await?;
+    } else {
+        print_groups(&groups);
+    }
+
+    Ok(())
+}
+
+fn print_groups(groups: &[ErrorGroup]) {
+    let rows: Vec<Vec<String>> = groups
+        .iter()
+        .map(|x| {
+            vec![
+                x.count.to_string(),
+                x.level.to_string(),
+                x.first.to_string(),
+                x.last.to_string(),
+                x.servers.iter().cloned().collect::<Vec<_>>().join(","),
+                x.message.clone(),
+            ]
+        })
+        .collect();
+
+    print_table(
+        &["COUNT", "LEVEL", "FIRST", "LAST", "SERVERS", "MESSAGE"],
+        &rows,
+    );
+}
+
+/// Picks a group to open its raw lines in the pager, until the picker is left with Esc.
+async fn browse_groups(groups: &[ErrorGroup]) -> Result<()> {
+    if groups.is_empty() {
+        info!("No entries found...");
+        return Ok(());
+    }
+
+    let total: u64 = groups.iter().map(|x| x.count).sum();
+    let mut selections = vec![format!("{total:>6}  All entries")];
+
+    for group in groups {
+        let mut message: String = group.message.chars().take(PICKER_MESSAGE_CHARS).collect();
+
+        if message.len() < group.message.len() {
+            message.push_str("...");
+        }
+
+        selections.push(format!(
+            "{:>6}  [{}] {message}  (last {})",
+            group.count, group.level, group.last
+        ));
+    }
+
+    loop {
+        let Some(selection) = FuzzySelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Pick messages to view, Esc to go back")
+            .default(0)
+            .items(&selections[..])
+            .interact_opt()?
+        else {
+            return Ok(());
+        };
+
+        let (lines, count, prompt) = match selection {
+            0 => {
+                let mut lines: Vec<&(NaiveDateTime, String)> =
+                    groups.iter().flat_map(|x| &x.lines).collect();
+                lines.sort_by_key(|x| Reverse(x.0));
+
+                let lines: Vec<String> = lines.into_iter().map(|x| x.1.clone()).collect();
+                (lines, total, "All entries".to_string())
+            }
+            index => {
+                let group = &groups[index - 1];
+                let lines: Vec<String> = group.lines.iter().map(|x| x.1.clone()).collect();
+                (
+                    lines,
+                    group.count,
+                    format!("[{}] {}", group.level, group.message),
+                )
+            }
+        };
+
+        let prompt = match lines.len() as u64 {
+            shown if shown < count => format!("{prompt} ({shown} of {count} lines)"),
+            _ => prompt,
+        };
+
+        cli_pager(Box::new(lines.into_iter().map(Ok)), &prompt).await?;
+    }
+}
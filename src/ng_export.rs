@@ -0,0 +1,364 @@
+use crate::access_log::{log_format_for, AccessEntry};
+use crate::geoip::{GeoIp, IpInfo};
+use crate::log_filter::Filter;
+use crate::log_ignore::Ignore;
+use crate::log_reader::{by_age, log_files, oldest_first, rotated_from};
+use crate::ng_view_logs::{ask_filter, find_log, pick_log_files};
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+/// The typed fields of `AccessEntry`, the variables of the log format are left out.
+const CSV_COLUMNS: [&str; 11] = [
+    "time",
+    "remote_addr",
+    "method",
+    "path",
+    "protocol",
+    "status",
+    "bytes",
+    "referer",
+    "user_agent",
+    "host",
+    "request_time",
+];
+
+/// Added with a GeoIP database.
+const CSV_IP_INFO_COLUMNS: [&str; 3] = ["country", "asn", "as_org"];
+
+/// A JSON Lines object, the entry with the country and network of its client.
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a AccessEntry,
+    #[serde(flatten)]
+    ip_info: Option<IpInfo>,
+}
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, EnumIter, EnumString)]
+pub enum ExportFormat {
+    #[strum(serialize = "csv")]
+    Csv,
+    /// One JSON object per request, also known as NDJSON.
+    #[strum(to_string = "jsonl", serialize = "ndjson")]
+    Jsonl,
+}
+
+/// Writes the requests of `files` matching `filter` to `out`, oldest first. Ignored
+/// lines and lines that don't fit the log format are skipped. With a GeoIP database,
+/// the country and network of the client are added. Returns the number of requests
+/// written.
+pub fn export_entries(
+    files: &[PathBuf],
+    filter: Option<&Filter>,
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> Result<u64> {
+    let ignore = Ignore::from_config()?;
+    let geoip = GeoIp::from_config()?;
+    let mut files = files.to_vec();
+    files.sort_by(|a, b| by_age(b, a));
+
+    let mut written = 0;
+    let mut unparsed = 0;
+
+    if format == ExportFormat::Csv {
+        let mut columns = CSV_COLUMNS.to_vec();
+
+        if geoip.is_some() {
+            columns.extend(CSV_IP_INFO_COLUMNS);
+        }
+
+        writeln!(out, "{}", columns.join(","))?;
+    }
+
+    for file_path in &files {
+        let log_format = log_format_for(file_path);
+
+        for line in oldest_first(file_path)? {
+            let line = line?;
+            let entry = log_format.parse(&line);
+
+            if ignore.matches(&line, entry.as_ref()) {
+                continue;
+            }
+
+            let Some(entry) = entry else {
+                unparsed += 1;
+                continue;
+            };
+
+            if filter.is_some_and(|filter| !filter.matches(&entry)) {
+                continue;
+            }
+
+            let ip_info = geoip.as_ref().map(|geoip| {
+                entry
+                    .remote_addr
+                    .map(|x| geoip.lookup(x))
+                    .unwrap_or_default()
+            });
+
+            match format {
+                ExportFormat::Csv => writeln!(out, "{}", csv_row(&entry, ip_info.as_ref()))?,
+                ExportFormat::Jsonl => {
+                    let entry = JsonEntry {
+                        entry: &entry,
+                        ip_info,
+                    };
+
+                    writeln!(out, "{}", serde_json::to_string(&entry)?)?;
+                }
+            }
+
+            written += 1;
+        }
+    }
+
+    out.flush()?;
+
+    if unparsed > 0 {
+        warn!("{unparsed} line(s) didn't match the log format and were skipped...");
+    }
+
+    Ok(written)
+}
+
+fn csv_row(
+    entry: &AccessEntry,
+    ip_info: Option<&IpInfo>,
+) -> String {
+    let text = |x: &Option<String>| x.as_deref().map(csv_text).unwrap_or_default();
+    let number = |x: Option<String>| x.unwrap_or_default();
+
+    let mut row = vec![
+        number(entry.time.map(|x| x.to_rfc3339())),
+        number(entry.remote_addr.map(|x| x.to_string())),
+        text(&entry.method),
+        text(&entry.path),
+        text(&entry.protocol),
+        number(entry.status.map(|x| x.to_string())),
+        number(entry.bytes.map(|x| x.to_string())),
+        text(&entry.referer),
+        text(&entry.user_agent),
+        text(&entry.host),
+        number(entry.request_time.map(|x| x.to_string())),
+    ];
+
+    if let Some(info) = ip_info {
+        row.extend([
+            text(&info.country),
+            number(info.asn.map(|x| x.to_string())),
+            text(&info.as_org),
+        ]);
+    }
+
+    row.join(",")
+}
+
+/// Quotes `value` when needed. Clients pick their paths, referers and user agents, text
+/// starting like a formula is prefixed with `'` so spreadsheets don't run it.
+fn csv_text(value: &str) -> String {
+    let value = match value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        true => format!("'{value}"),
+        false => value.to_string(),
+    };
+
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value,
+    }
+}
+
+fn export_to_file(
+    files: &[PathBuf],
+    filter: Option<&Filter>,
+    format: ExportFormat,
+    output: &Path,
+) -> Result<()> {
+    if is_one_of(output, files) {
+        return Err(anyhow!(
+            "Won't export to {}, it is one of the exported logs",
+            output.display()
+        ));
+    }
+
+    let file =
+        File::create(output).with_context(|| format!("Couldn't create {}", output.display()))?;
+    let written = export_entries(files, filter, format, &mut BufWriter::new(file))?;
+
+    info!("Exported {written} request(s) to {}...", output.display());
+
+    Ok(())
+}
+
+/// Whether `output` is one of `files`, which would be truncated before it is read.
+fn is_one_of(
+    output: &Path,
+    files: &[PathBuf],
+) -> bool {
+    let Ok(output) = output.canonicalize() else {
+        return false;
+    };
+
+    files
+        .iter()
+        .any(|x| x.canonicalize().is_ok_and(|x| x == output))
+}
+
+pub async fn ng_export() -> Result<()> {
+    let Some(files) = pick_log_files().await? else {
+        info!("No logs found...");
+        return Ok(());
+    };
+
+    let filter = ask_filter()?;
+    let files: Vec<PathBuf> = files.iter().map(|x| PathBuf::from(&x.file_path)).collect();
+
+    ask_export(&files, filter.as_ref())
+}
+
+/// Asks for a format and a file to export the requests of `files` to, also used by the
+/// log viewer to export what it shows.
+pub fn ask_export(
+    files: &[PathBuf],
+    filter: Option<&Filter>,
+) -> Result<()> {
+    let formats: Vec<ExportFormat> = ExportFormat::iter().collect();
+    let format = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Export as")
+        .default(0)
+        .items(&formats[..])
+        .interact()?;
+    let format = formats[format];
+
+    let name = files
+        .first()
+        .and_then(|x| rotated_from(x).file_name().map(|x| x.to_owned()))
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let output: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Export to")
+        .default(format!("{name}.{format}"))
+        .interact_text()?;
+    let output = PathBuf::from(output);
+
+    if output.exists()
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Overwrite {}?", output.display()))
+            .default(false)
+            .interact()?
+    {
+        info!("Nothing exported...");
+        return Ok(());
+    }
+
+    export_to_file(files, filter, format, &output)
+}
+
+pub async fn export(
+    files: Vec<String>,
+    rotated: bool,
+    filter: Option<String>,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let filter = filter.as_deref().map(Filter::parse).transpose()?;
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in &files {
+        paths.push(PathBuf::from(find_log(file).await?.file_path));
+    }
+
+    let paths = log_files(&paths, rotated)?;
+
+    if let Some(output) = output {
+        return export_to_file(&paths, filter.as_ref(), format, &output);
+    }
+
+    let stdout = io::stdout().lock();
+
+    match export_entries(&paths, filter.as_ref(), format, &mut BufWriter::new(stdout)) {
+        // Piped into `head`, which stopped reading.
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|x| x.kind() == ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn quotes_csv_text() {
+        assert_eq!(csv_text("/index.html"), "/index.html");
+        assert_eq!(csv_text(""), "");
+        assert_eq!(
+            csv_text("Mozilla/5.0 (X11, Linux)"),
+            "\"Mozilla/5.0 (X11, Linux)\""
+        );
+        assert_eq!(csv_text("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_text("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn defuses_csv_formulas() {
+        assert_eq!(csv_text("=1+1"), "'=1+1");
+        assert_eq!(csv_text("+1"), "'+1");
+        assert_eq!(csv_text("-"), "'-");
+        assert_eq!(csv_text("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_text("\tcmd"), "'\tcmd");
+        assert_eq!(
+            csv_text("=HYPERLINK(\"https://evil.test/?a,b\")"),
+            "\"'=HYPERLINK(\"\"https://evil.test/?a,b\"\")\""
+        );
+        assert_eq!(csv_text("a=b"), "a=b");
+    }
+
+    #[test]
+    fn writes_csv_rows() {
+        let entry = AccessEntry {
+            remote_addr: Some("10.0.0.7".parse().unwrap()),
+            method: Some("GET".to_string()),
+            path: Some("/search?q=a,b".to_string()),
+            status: Some(200),
+            user_agent: Some("=cmd|' /C calc'!A0".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            csv_row(&entry, None),
+            ",10.0.0.7,GET,\"/search?q=a,b\",,200,,,'=cmd|' /C calc'!A0,,"
+        );
+    }
+
+    #[test]
+    fn finds_outputs_that_are_exported_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("access.log");
+        let output = dir.path().join("export.csv");
+
+        let files = [log.clone()];
+
+        fs::write(&log, "").unwrap();
+
+        assert!(is_one_of(&dir.path().join("./access.log"), &files));
+        assert!(!is_one_of(&output, &files));
+
+        fs::write(&output, "").unwrap();
+
+        assert!(!is_one_of(&output, &files));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::site::{list_sites, SiteSummary};
+use crate::utils::print_table;
+use anyhow::Result;
+
+pub async fn ng_list_sites(json: bool) -> Result<()> {
+    let sites = list_sites().await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sites)?);
+        return Ok(());
+    }
+
+    if sites.is_empty() {
+        info!("No sites found...");
+        return Ok(());
+    }
+
+    let headers = [
+        "SITE",
+        "ENABLED",
+        "SERVER NAMES",
+        "LISTEN",
+        "TARGET",
+        "LOGS",
+    ];
+    let rows: Vec<Vec<String>> = sites.iter().flat_map(site_rows).collect();
+
+    print_table(&headers, &rows);
+
+    for site in &sites {
+        if let Some(error) = &site.error {
+            warn!("Failed to parse {}: {error}", site.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// One row per `server` block, the site name is only shown on the first.
+fn site_rows(site: &SiteSummary) -> Vec<Vec<String>> {
+    let enabled = if site.enabled { "yes" } else { "no" };
+
+    if site.servers.is_empty() {
+        return vec![vec![site.name.clone(), enabled.into()]];
+    }
+
+    site.servers
+        .iter()
+        .enumerate()
+        .map(|(index, server)| {
+            let (name, enabled) = match index {
+                0 => (site.name.clone(), enabled.to_string()),
+                _ => (String::new(), String::new()),
+            };
+
+            let target: Vec<String> = server
+                .roots
+                .iter()
+                .chain(server.proxy_pass.iter())
+                .cloned()
+                .collect();
+
+            let logs: Vec<String> = server
+                .access_logs
+                .iter()
+                .chain(server.error_logs.iter())
+                .cloned()
+                .collect();
+
+            vec![
+                name,
+                enabled,
+                server.server_names.join(" "),
+                server
+                    .listen
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                target.join(", "),
+                logs.join(", "),
+            ]
+        })
+        .collect()
+}
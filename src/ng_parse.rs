@@ -0,0 +1,22 @@
+use crate::parser::{parse_file, parse_file_with_includes};
+use crate::utils::nginx_conf_file;
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// Prints the parsed config as JSON, defaults to the configured nginx.conf.
+pub fn ng_parse(
+    file: Option<String>,
+    no_includes: bool,
+) -> Result<()> {
+    let path = file.map_or_else(nginx_conf_file, PathBuf::from);
+
+    let config = if no_includes {
+        parse_file(&path)?
+    } else {
+        parse_file_with_includes(&path)?
+    };
+
+    println!("{}", serde_json::to_string_pretty(&config)?);
+
+    Ok(())
+}
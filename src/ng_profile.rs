@@ -0,0 +1,44 @@
+use crate::config::{active_profile, set_profile, CONFIG};
+use anyhow::Result;
+use dialoguer::{theme::ColorfulTheme, Select};
+use std::iter::once;
+
+/// Menu entry of the top-level `[paths]` and `[service]`, which aren't a profile.
+const NO_PROFILE: &str = "no profile ([paths] and [service])";
+
+pub fn ng_profile() -> Result<()> {
+    if CONFIG.profiles.is_empty() {
+        info!("No profiles found in config...");
+        return Ok(());
+    }
+
+    let profiles: Vec<Option<&String>> =
+        once(None).chain(CONFIG.profiles.keys().map(Some)).collect();
+    let selections: Vec<&str> = profiles
+        .iter()
+        .map(|x| x.map_or(NO_PROFILE, |x| x.as_str()))
+        .collect();
+
+    let active = active_profile();
+    let default = profiles
+        .iter()
+        .position(|x| x.cloned() == active)
+        .unwrap_or(0);
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Pick profile")
+        .default(default)
+        .items(&selections[..])
+        .interact()?;
+
+    let profile = profiles[selection].cloned();
+
+    set_profile(profile.clone())?;
+
+    match profile {
+        Some(name) => info!("Using profile {name}..."),
+        None => info!("Using {NO_PROFILE}..."),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,451 @@
+use crate::access_log::{log_format_for, AccessEntry};
+use crate::config::{ScannerRules, CONFIG};
+use crate::geoip::{GeoIp, IpInfo};
+use crate::log_filter::Filter;
+use crate::log_ignore::Ignore;
+use crate::log_reader::{log_files, open_log};
+use crate::ng_view_logs::{find_log, pick_log_files};
+use crate::transaction::SitesTransaction;
+use crate::utils::print_table;
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local, Timelike};
+use dialoguer::{theme::ColorfulTheme, Select};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+/// Written to the nginxconfig.io directory, next to the snippets of `ngsite defaults`.
+const SNIPPET_FILE: &str = "scanners.conf";
+/// Set to 1 for scanners by the `geo` and `map` snippets.
+const SNIPPET_VARIABLE: &str = "$ngsite_scanner";
+/// Probe paths and user agents kept per client, the counts include the rest.
+const EXAMPLES: usize = 5;
+
+/// Why a client was flagged.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Reason {
+    /// Requested one of `probe_paths`.
+    Probe,
+    /// Sent one of `bad_user_agents`.
+    Agent,
+    /// Got `burst_4xx` 4xx responses within a minute.
+    Burst,
+}
+
+/// A client flagged as a scanner.
+#[derive(Debug, Clone, Serialize)]
+pub struct Scanner {
+    pub ip: IpAddr,
+    pub reasons: Vec<Reason>,
+    pub requests: u64,
+    pub client_errors: u64,
+    /// Most 4xx responses within one minute.
+    pub burst_4xx: u64,
+    pub probe_requests: u64,
+    /// Some of the probe paths it requested.
+    pub probe_paths: Vec<String>,
+    pub bad_user_agents: Vec<String>,
+    pub first: Option<DateTime<FixedOffset>>,
+    pub last: Option<DateTime<FixedOffset>>,
+    /// With a GeoIP database.
+    pub ip_info: Option<IpInfo>,
+}
+
+/// How the snippet blocks the flagged clients.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, EnumIter, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum SnippetKind {
+    /// `deny` directives, included in a server or location block.
+    Deny,
+    /// A `geo` block setting `$ngsite_scanner`, included in the http block.
+    Geo,
+    /// A `map` of `$remote_addr` setting `$ngsite_scanner`, included in the http block.
+    Map,
+}
+
+/// `ScannerRules` with compiled regexes.
+struct Rules {
+    probe_paths: Vec<Regex>,
+    bad_user_agents: Vec<Regex>,
+    burst_4xx: u64,
+    min_4xx_ratio: f64,
+}
+
+#[derive(Debug, Default)]
+struct ClientTally {
+    requests: u64,
+    client_errors: u64,
+    client_errors_per_minute: HashMap<DateTime<FixedOffset>, u64>,
+    probe_requests: u64,
+    probe_paths: BTreeSet<String>,
+    bad_user_agents: BTreeSet<String>,
+    first: Option<DateTime<FixedOffset>>,
+    last: Option<DateTime<FixedOffset>>,
+}
+
+impl Rules {
+    fn new(rules: &ScannerRules) -> Result<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|x| Regex::new(x).with_context(|| format!("Invalid regex: {x}")))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            probe_paths: compile(&rules.probe_paths).context("Invalid scanners.probe_paths")?,
+            bad_user_agents: compile(&rules.bad_user_agents)
+                .context("Invalid scanners.bad_user_agents")?,
+            burst_4xx: rules.burst_4xx,
+            min_4xx_ratio: rules.min_4xx_ratio,
+        })
+    }
+
+    fn reasons(
+        &self,
+        tally: &ClientTally,
+        burst_4xx: u64,
+    ) -> Vec<Reason> {
+        let mut reasons = vec![];
+
+        if tally.probe_requests > 0 {
+            reasons.push(Reason::Probe);
+        }
+
+        if !tally.bad_user_agents.is_empty() {
+            reasons.push(Reason::Agent);
+        }
+
+        let ratio = tally.client_errors as f64 / tally.requests as f64;
+
+        if burst_4xx >= self.burst_4xx.max(1) && ratio >= self.min_4xx_ratio {
+            reasons.push(Reason::Burst);
+        }
+
+        reasons
+    }
+}
+
+impl ClientTally {
+    fn add(
+        &mut self,
+        entry: &AccessEntry,
+        rules: &Rules,
+    ) {
+        self.requests += 1;
+
+        if let Some(time) = entry.time {
+            self.first = self.first.min(Some(time)).or(Some(time));
+            self.last = self.last.max(Some(time));
+        }
+
+        if entry.status.is_some_and(|x| (400..500).contains(&x)) {
+            self.client_errors += 1;
+
+            if let Some(time) = entry.time {
+                let minute = time.with_second(0).and_then(|x| x.with_nanosecond(0));
+                *self
+                    .client_errors_per_minute
+                    .entry(minute.unwrap_or(time))
+                    .or_default() += 1;
+            }
+        }
+
+        if let Some(path) = &entry.path {
+            if rules.probe_paths.iter().any(|x| x.is_match(path)) {
+                self.probe_requests += 1;
+
+                if self.probe_paths.len() < EXAMPLES {
+                    self.probe_paths.insert(path.clone());
+                }
+            }
+        }
+
+        if let Some(user_agent) = &entry.user_agent {
+            if self.bad_user_agents.len() < EXAMPLES
+                && rules.bad_user_agents.iter().any(|x| x.is_match(user_agent))
+            {
+                self.bad_user_agents.insert(user_agent.clone());
+            }
+        }
+    }
+}
+
+/// Reads every file in full and flags the clients that look like scanners, the most
+/// suspicious first.
+pub fn find_scanners(
+    files: &[PathBuf],
+    filter: Option<&Filter>,
+) -> Result<Vec<Scanner>> {
+    let rules = Rules::new(&CONFIG.scanners)?;
+    let ignore = Ignore::from_config()?;
+    let geoip = GeoIp::from_config()?;
+
+    let mut clients: HashMap<IpAddr, ClientTally> = HashMap::new();
+
+    for file_path in files {
+        let format = log_format_for(file_path);
+
+        for line in open_log(file_path)? {
+            let line = line?;
+            let entry = format.parse(&line);
+
+            if ignore.matches(&line, entry.as_ref()) {
+                continue;
+            }
+
+            let Some(entry) = entry else {
+                continue;
+            };
+
+            if filter.is_some_and(|filter| !filter.matches(&entry)) {
+                continue;
+            }
+
+            if let Some(ip) = entry.remote_addr {
+                clients.entry(ip).or_default().add(&entry, &rules);
+            }
+        }
+    }
+
+    let mut scanners: Vec<Scanner> = clients
+        .into_iter()
+        .filter_map(|(ip, tally)| {
+            let burst_4xx = tally
+                .client_errors_per_minute
+                .values()
+                .max()
+                .copied()
+                .unwrap_or(0);
+            let reasons = rules.reasons(&tally, burst_4xx);
+
+            if reasons.is_empty() {
+                return None;
+            }
+
+            Some(Scanner {
+                ip,
+                reasons,
+                requests: tally.requests,
+                client_errors: tally.client_errors,
+                burst_4xx,
+                probe_requests: tally.probe_requests,
+                probe_paths: tally.probe_paths.into_iter().collect(),
+                bad_user_agents: tally.bad_user_agents.into_iter().collect(),
+                first: tally.first,
+                last: tally.last,
+                ip_info: geoip.as_ref().map(|x| x.lookup(ip)),
+            })
+        })
+        .collect();
+
+    scanners.sort_by(|a, b| {
+        b.reasons
+            .len()
+            .cmp(&a.reasons.len())
+            .then(b.requests.cmp(&a.requests))
+            .then(a.ip.cmp(&b.ip))
+    });
+
+    Ok(scanners)
+}
+
+pub async fn ng_scanners() -> Result<()> {
+    let Some(files) = pick_log_files().await? else {
+        info!("No logs found...");
+        return Ok(());
+    };
+
+    let files: Vec<PathBuf> = files.iter().map(|x| PathBuf::from(&x.file_path)).collect();
+    let scanners = find_scanners(&files, None)?;
+
+    if scanners.is_empty() {
+        info!("No scanners found...");
+        return Ok(());
+    }
+
+    print_scanners(&scanners);
+
+    let mut selections = vec!["Don't write a snippet".to_string()];
+    selections.extend(SnippetKind::iter().map(|x| format!("Write a {x} snippet")));
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("Block them in nginxconfig.io/{SNIPPET_FILE}?"))
+        .default(0)
+        .items(&selections[..])
+        .interact()?;
+
+    match SnippetKind::iter().nth(selection.wrapping_sub(1)) {
+        Some(kind) => write_snippet(&scanners, kind),
+        None => Ok(()),
+    }
+}
+
+pub async fn scanners(
+    files: Vec<String>,
+    rotated: bool,
+    filter: Option<String>,
+    snippet: Option<SnippetKind>,
+    json: bool,
+) -> Result<()> {
+    let filter = filter.as_deref().map(Filter::parse).transpose()?;
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in &files {
+        paths.push(PathBuf::from(find_log(file).await?.file_path));
+    }
+
+    let scanners = find_scanners(&log_files(&paths, rotated)?, filter.as_ref())?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&scanners)?);
+    } else if scanners.is_empty() {
+        info!("No scanners found...");
+    } else {
+        print_scanners(&scanners);
+    }
+
+    match snippet {
+        Some(kind) => write_snippet(&scanners, kind),
+        None => Ok(()),
+    }
+}
+
+fn print_scanners(scanners: &[Scanner]) {
+    let has_ip_info = scanners.iter().any(|x| x.ip_info.is_some());
+
+    let rows: Vec<Vec<String>> = scanners
+        .iter()
+        .map(|x| {
+            let reasons: Vec<String> = x.reasons.iter().map(|x| x.to_string()).collect();
+            let mut row = vec![
+                x.ip.to_string(),
+                reasons.join(","),
+                x.requests.to_string(),
+                x.client_errors.to_string(),
+                x.burst_4xx.to_string(),
+            ];
+
+            if has_ip_info {
+                row.push(x.ip_info.clone().unwrap_or_default().to_string());
+            }
+
+            row.push(
+                x.probe_paths
+                    .iter()
+                    .chain(&x.bad_user_agents)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+
+            row
+        })
+        .collect();
+
+    let mut headers = vec!["IP", "REASONS", "REQUESTS", "4XX", "4XX/MIN"];
+
+    if has_ip_info {
+        headers.push("NETWORK");
+    }
+
+    headers.push("PROBES AND AGENTS");
+
+    print_table(&headers, &rows);
+}
+
+/// Replaces `nginxconfig.io/scanners.conf` with a snippet blocking `scanners`, restoring
+/// it when nginx fails its test, as it may already be included.
+fn write_snippet(
+    scanners: &[Scanner],
+    kind: SnippetKind,
+) -> Result<()> {
+    let file_path = Path::new(&CONFIG.paths().nginx)
+        .join("nginxconfig.io")
+        .join(SNIPPET_FILE);
+
+    let mut transaction = SitesTransaction::begin()?;
+
+    // Only a wildcard include picks up a new file, the rollback removes it.
+    match file_path.exists() {
+        true => transaction.backup_file(&file_path)?,
+        false => transaction.created_file(&file_path),
+    }
+
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(&file_path, snippet(scanners, kind))?;
+    transaction.touch(SNIPPET_FILE);
+    transaction.commit()?;
+
+    info!(
+        "Wrote a {kind} snippet blocking {} client(s) to {}...",
+        scanners.len(),
+        file_path.display()
+    );
+
+    match kind {
+        SnippetKind::Deny => {
+            info!("Include nginxconfig.io/{SNIPPET_FILE} in a server block and reload nginx...")
+        }
+        SnippetKind::Geo | SnippetKind::Map => info!(
+            "Include nginxconfig.io/{SNIPPET_FILE} in the http block, add \
+             `if ({SNIPPET_VARIABLE}) {{ return 403; }}` to a server block and reload nginx..."
+        ),
+    }
+
+    Ok(())
+}
+
+fn snippet(
+    scanners: &[Scanner],
+    kind: SnippetKind,
+) -> String {
+    let mut snippet = format!(
+        "# Scanners found by ngsite on {}, regenerated by `ngsite scanners --snippet {kind}`.\n",
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+
+    let entry = |x: &Scanner| {
+        let reasons: Vec<String> = x.reasons.iter().map(|x| x.to_string()).collect();
+        (x.ip, reasons.join(","))
+    };
+
+    match kind {
+        SnippetKind::Deny => {
+            for (ip, reasons) in scanners.iter().map(entry) {
+                let _ = writeln!(snippet, "deny {ip}; # {reasons}");
+            }
+        }
+        SnippetKind::Geo | SnippetKind::Map => {
+            let block = match kind {
+                SnippetKind::Map => format!("map $remote_addr {SNIPPET_VARIABLE}"),
+                _ => format!("geo {SNIPPET_VARIABLE}"),
+            };
+
+            let _ = writeln!(snippet, "{block} {{\n    default 0;");
+
+            for (ip, reasons) in scanners.iter().map(entry) {
+                // `map` compares strings, `::1` would be a hostname or regex otherwise.
+                match kind {
+                    SnippetKind::Map => writeln!(snippet, "    \"{ip}\" 1; # {reasons}"),
+                    _ => writeln!(snippet, "    {ip} 1; # {reasons}"),
+                }
+                .ok();
+            }
+
+            snippet.push_str("}\n");
+        }
+    }
+
+    snippet
+}
@@ -1,8 +1,11 @@
+use crate::config::active_profile;
 use dialoguer::{theme::ColorfulTheme, Select};
 use strum::{Display, EnumIter, IntoEnumIterator};
 
 #[derive(Debug, Display, Clone, Copy, EnumIter)]
 pub enum NgSelect {
+    #[strum(serialize = "List Sites")]
+    List,
     #[strum(serialize = "Create Default")]
     NgDefault,
     #[strum(serialize = "Enable Site")]
@@ -13,20 +16,40 @@ pub enum NgSelect {
     ViewSite,
     #[strum(serialize = "View Log")]
     ViewLog,
+    #[strum(serialize = "Follow Logs")]
+    FollowLog,
+    #[strum(serialize = "Log Stats")]
+    Stats,
+    #[strum(serialize = "Error Log")]
+    ErrorLog,
+    #[strum(serialize = "Export Log")]
+    Export,
+    #[strum(serialize = "Scanner Report")]
+    Scanners,
     #[strum(serialize = "Edit Site")]
     Edit,
+    #[strum(serialize = "Doctor")]
+    Doctor,
     #[strum(serialize = "Test Nginx")]
     Test,
     #[strum(serialize = "Reload Nginx")]
     Reload,
+    #[strum(serialize = "Restart Nginx")]
+    Restart,
+    #[strum(serialize = "Switch Profile")]
+    Profile,
     Exit,
 }
 
 pub fn ng_select() -> NgSelect {
     let selections: Vec<_> = NgSelect::iter().collect();
+    let prompt = match active_profile() {
+        Some(profile) => format!("[{profile}]"),
+        None => String::new(),
+    };
 
     let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("")
+        .with_prompt(prompt)
         .default(0)
         .items(&selections[..])
         .interact()
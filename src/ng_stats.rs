@@ -0,0 +1,453 @@
+use crate::access_log::{log_format_for, AccessEntry};
+use crate::geoip::{GeoIp, IpInfo};
+use crate::log_filter::Filter;
+use crate::log_ignore::Ignore;
+use crate::log_reader::{log_files, open_log};
+use crate::ng_view_logs::{ask_filter, find_log, pick_log_files};
+use crate::utils::print_table;
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, Timelike};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// Rows of the requests per minute table, minutes are grouped to stay below it.
+const HISTOGRAM_ROWS: i64 = 60;
+const HISTOGRAM_WIDTH: u64 = 50;
+
+#[derive(Debug, Default, Serialize)]
+pub struct LogStats {
+    pub files: Vec<PathBuf>,
+    pub requests: u64,
+    /// Lines that don't fit the log format.
+    pub unparsed: u64,
+    pub bytes: u64,
+    pub first: Option<DateTime<FixedOffset>>,
+    pub last: Option<DateTime<FixedOffset>>,
+    /// `2xx`, `4xx`, ...
+    pub status_classes: BTreeMap<String, u64>,
+    pub top_paths: Vec<Count>,
+    pub top_ips: Vec<Count>,
+    /// Country and network of `top_ips`, empty without a GeoIP database.
+    pub ip_info: BTreeMap<String, IpInfo>,
+    pub top_countries: Vec<Count>,
+    /// `AS3320 Deutsche Telekom AG`
+    pub top_networks: Vec<Count>,
+    pub top_user_agents: Vec<Count>,
+    pub top_referers: Vec<Count>,
+    pub requests_per_minute: BTreeMap<DateTime<FixedOffset>, u64>,
+    /// Empty unless the format logs `$request_time`.
+    pub slowest: Vec<SlowRequest>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Count {
+    pub value: String,
+    pub requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowRequest {
+    pub time: Option<DateTime<FixedOffset>>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<u16>,
+    pub request_time: f64,
+}
+
+/// Running totals, turned into `LogStats` once every line is read.
+#[derive(Debug, Default)]
+struct Tally {
+    stats: LogStats,
+    paths: HashMap<String, u64>,
+    ips: HashMap<String, u64>,
+    user_agents: HashMap<String, u64>,
+    referers: HashMap<String, u64>,
+}
+
+impl Tally {
+    fn add(
+        &mut self,
+        entry: AccessEntry,
+        top: usize,
+    ) {
+        let stats = &mut self.stats;
+
+        stats.requests += 1;
+        stats.bytes += entry.bytes.unwrap_or(0);
+
+        if let Some(status) = entry.status {
+            *stats
+                .status_classes
+                .entry(format!("{}xx", status / 100))
+                .or_default() += 1;
+        }
+
+        if let Some(time) = entry.time {
+            stats.first = stats.first.min(Some(time)).or(Some(time));
+            stats.last = stats.last.max(Some(time));
+
+            let minute = time.with_second(0).and_then(|x| x.with_nanosecond(0));
+            *stats
+                .requests_per_minute
+                .entry(minute.unwrap_or(time))
+                .or_default() += 1;
+        }
+
+        // Queries are left out, `/search?q=a` and `/search?q=b` are the same page.
+        if let Some(path) = &entry.path {
+            let path = path.split_once('?').map_or(path.as_str(), |(path, _)| path);
+            *self.paths.entry(path.to_string()).or_default() += 1;
+        }
+
+        if let Some(ip) = entry.remote_addr {
+            *self.ips.entry(ip.to_string()).or_default() += 1;
+        }
+
+        if let Some(user_agent) = &entry.user_agent {
+            *self.user_agents.entry(user_agent.clone()).or_default() += 1;
+        }
+
+        if let Some(referer) = &entry.referer {
+            *self.referers.entry(referer.clone()).or_default() += 1;
+        }
+
+        if let Some(request_time) = entry.request_time {
+            stats.slowest.push(SlowRequest {
+                time: entry.time,
+                method: entry.method,
+                path: entry.path,
+                status: entry.status,
+                request_time,
+            });
+
+            // Sorting on every request is too slow for big logs.
+            if stats.slowest.len() > top * 2 {
+                sort_slowest(&mut stats.slowest, top);
+            }
+        }
+    }
+
+    fn finish(
+        mut self,
+        top: usize,
+        geoip: Option<&GeoIp>,
+    ) -> LogStats {
+        sort_slowest(&mut self.stats.slowest, top);
+
+        let mut ip_info: HashMap<String, IpInfo> = HashMap::new();
+        let mut countries: HashMap<String, u64> = HashMap::new();
+        let mut networks: HashMap<String, u64> = HashMap::new();
+
+        if let Some(geoip) = geoip {
+            for (ip, requests) in &self.ips {
+                let Ok(address) = ip.parse() else {
+                    continue;
+                };
+
+                let info = geoip.lookup(address);
+
+                if let Some(country) = &info.country {
+                    *countries.entry(country.clone()).or_default() += requests;
+                }
+
+                if let Some(network) = info.network() {
+                    *networks.entry(network).or_default() += requests;
+                }
+
+                ip_info.insert(ip.clone(), info);
+            }
+        }
+
+        self.stats.top_paths = top_counts(self.paths, top);
+        self.stats.top_ips = top_counts(self.ips, top);
+        self.stats.top_countries = top_counts(countries, top);
+        self.stats.top_networks = top_counts(networks, top);
+        self.stats.top_user_agents = top_counts(self.user_agents, top);
+        self.stats.top_referers = top_counts(self.referers, top);
+
+        for count in &self.stats.top_ips {
+            if let Some(info) = ip_info.remove(&count.value) {
+                self.stats.ip_info.insert(count.value.clone(), info);
+            }
+        }
+
+        self.stats
+    }
+}
+
+fn sort_slowest(
+    slowest: &mut Vec<SlowRequest>,
+    top: usize,
+) {
+    slowest.sort_by(|a, b| b.request_time.total_cmp(&a.request_time));
+    slowest.truncate(top);
+}
+
+fn top_counts(
+    counts: HashMap<String, u64>,
+    top: usize,
+) -> Vec<Count> {
+    let mut counts: Vec<Count> = counts
+        .into_iter()
+        .map(|(value, requests)| Count { value, requests })
+        .collect();
+
+    counts.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.value.cmp(&b.value)));
+    counts.truncate(top);
+
+    counts
+}
+
+/// Reads every file in full, each with the log format nginx writes it in.
+pub fn log_stats(
+    files: &[PathBuf],
+    filter: Option<&Filter>,
+    top: usize,
+) -> Result<LogStats> {
+    let ignore = Ignore::from_config()?;
+    let geoip = GeoIp::from_config()?;
+    let mut tally = Tally::default();
+
+    for file_path in files {
+        let format = log_format_for(file_path);
+
+        for line in open_log(file_path)? {
+            let line = line?;
+
+            let entry = format.parse(&line);
+
+            if ignore.matches(&line, entry.as_ref()) {
+                continue;
+            }
+
+            let Some(entry) = entry else {
+                tally.stats.unparsed += 1;
+                continue;
+            };
+
+            if filter.is_some_and(|filter| !filter.matches(&entry)) {
+                continue;
+            }
+
+            tally.add(entry, top);
+        }
+    }
+
+    tally.stats.files = files.to_vec();
+
+    Ok(tally.finish(top, geoip.as_ref()))
+}
+
+pub async fn ng_stats() -> Result<()> {
+    let Some(files) = pick_log_files().await? else {
+        info!("No logs found...");
+        return Ok(());
+    };
+
+    let filter = ask_filter()?;
+    let files: Vec<PathBuf> = files.iter().map(|x| PathBuf::from(&x.file_path)).collect();
+
+    print_stats(&files, false, filter, 10, false)
+}
+
+pub async fn stats(
+    files: Vec<String>,
+    rotated: bool,
+    filter: Option<String>,
+    top: usize,
+    json: bool,
+) -> Result<()> {
+    let filter = filter.as_deref().map(Filter::parse).transpose()?;
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in &files {
+        paths.push(PathBuf::from(find_log(file).await?.file_path));
+    }
+
+    print_stats(&paths, rotated, filter, top, json)
+}
+
+fn print_stats(
+    files: &[PathBuf],
+    rotated: bool,
+    filter: Option<Filter>,
+    top: usize,
+    json: bool,
+) -> Result<()> {
+    let paths = log_files(files, rotated)?;
+    let stats = log_stats(&paths, filter.as_ref(), top)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    print_summary(&stats);
+    print_counts("TOP PATHS", "PATH", &stats.top_paths);
+    print_ips(&stats);
+    print_counts("TOP COUNTRIES", "COUNTRY", &stats.top_countries);
+    print_counts("TOP NETWORKS", "NETWORK", &stats.top_networks);
+    print_counts("TOP USER AGENTS", "USER AGENT", &stats.top_user_agents);
+    print_counts("TOP REFERERS", "REFERER", &stats.top_referers);
+    print_histogram(&stats);
+    print_slowest(&stats);
+
+    if stats.unparsed > 0 {
+        warn!(
+            "{} line(s) didn't match the log format and were skipped...",
+            stats.unparsed
+        );
+    }
+
+    Ok(())
+}
+
+fn print_summary(stats: &LogStats) {
+    let time = |x: Option<DateTime<FixedOffset>>| x.map(|x| x.to_string()).unwrap_or_default();
+    let files: Vec<String> = stats
+        .files
+        .iter()
+        .map(|x| x.display().to_string())
+        .collect();
+
+    let mut rows = vec![
+        vec!["Files".into(), files.join(", ")],
+        vec!["Requests".into(), stats.requests.to_string()],
+        vec!["Bandwidth".into(), format_bytes(stats.bytes)],
+        vec!["First".into(), time(stats.first)],
+        vec!["Last".into(), time(stats.last)],
+    ];
+
+    for (class, requests) in &stats.status_classes {
+        rows.push(vec![class.clone(), requests.to_string()]);
+    }
+
+    print_table(&["SUMMARY", ""], &rows);
+}
+
+fn print_counts(
+    title: &str,
+    header: &str,
+    counts: &[Count],
+) {
+    if counts.is_empty() {
+        return;
+    }
+
+    let rows: Vec<Vec<String>> = counts
+        .iter()
+        .map(|x| vec![x.requests.to_string(), x.value.clone()])
+        .collect();
+
+    println!("\n{title}");
+    print_table(&["REQUESTS", header], &rows);
+}
+
+/// Top client IPs, with their country and network when there is a GeoIP database.
+fn print_ips(stats: &LogStats) {
+    if stats.ip_info.is_empty() {
+        print_counts("TOP CLIENT IPS", "IP", &stats.top_ips);
+        return;
+    }
+
+    let rows: Vec<Vec<String>> = stats
+        .top_ips
+        .iter()
+        .map(|x| {
+            let info = stats.ip_info.get(&x.value).cloned().unwrap_or_default();
+
+            vec![
+                x.requests.to_string(),
+                x.value.clone(),
+                info.country.clone().unwrap_or_default(),
+                info.network().unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    println!("\nTOP CLIENT IPS");
+    print_table(&["REQUESTS", "IP", "COUNTRY", "NETWORK"], &rows);
+}
+
+/// Requests per minute, from the first to the last request.
+fn print_histogram(stats: &LogStats) {
+    let (Some((first, _)), Some((last, _))) = (
+        stats.requests_per_minute.first_key_value(),
+        stats.requests_per_minute.last_key_value(),
+    ) else {
+        return;
+    };
+
+    let minutes = (*last - *first).num_minutes() + 1;
+    let bucket = (minutes + HISTOGRAM_ROWS - 1) / HISTOGRAM_ROWS;
+
+    let mut buckets: BTreeMap<i64, u64> = BTreeMap::new();
+
+    for (minute, requests) in &stats.requests_per_minute {
+        let index = (*minute - *first).num_minutes() / bucket;
+        *buckets.entry(index).or_default() += requests;
+    }
+
+    let max = buckets.values().copied().max().unwrap_or(1);
+    let rows: Vec<Vec<String>> = (0..=(minutes - 1) / bucket)
+        .map(|index| {
+            let requests = buckets.get(&index).copied().unwrap_or(0);
+            let start = *first + Duration::minutes(index * bucket);
+            let per_minute = requests as f64 / bucket as f64;
+            let bar = "#".repeat((requests * HISTOGRAM_WIDTH / max) as usize);
+
+            vec![
+                start.format("%Y-%m-%d %H:%M").to_string(),
+                format!("{per_minute:.1}"),
+                bar,
+            ]
+        })
+        .collect();
+
+    match bucket {
+        1 => println!("\nREQUESTS PER MINUTE"),
+        _ => println!("\nREQUESTS PER MINUTE (averaged over {bucket} minutes)"),
+    }
+
+    print_table(&["MINUTE", "REQ/MIN", ""], &rows);
+}
+
+fn print_slowest(stats: &LogStats) {
+    if stats.slowest.is_empty() {
+        return;
+    }
+
+    let rows: Vec<Vec<String>> = stats
+        .slowest
+        .iter()
+        .map(|x| {
+            vec![
+                format!("{:.3}s", x.request_time),
+                x.status.map(|x| x.to_string()).unwrap_or_default(),
+                x.method.clone().unwrap_or_default(),
+                x.path.clone().unwrap_or_default(),
+                x.time.map(|x| x.to_string()).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    println!("\nSLOWEST REQUESTS");
+    print_table(&["TIME", "STATUS", "METHOD", "PATH", "AT"], &rows);
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{value:.1} {}", units[unit]),
+    }
+}
@@ -1,4 +1,5 @@
-use crate::utils::{reload_nginx, test_nginx};
+use crate::transaction::SitesTransaction;
+use crate::utils::reload_nginx;
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Select};
 use strum::{Display, EnumIter, IntoEnumIterator};
@@ -9,7 +10,8 @@ enum YesNo {
     No,
 }
 
-pub fn ng_test_reload() -> Result<()> {
+/// Asks to test and reload, rolling back `transaction` if the test fails.
+pub fn ng_test_reload(transaction: SitesTransaction) -> Result<()> {
     let selections: Vec<_> = YesNo::iter().collect();
 
     let selection = Select::with_theme(&ColorfulTheme::default())
@@ -21,7 +23,7 @@ pub fn ng_test_reload() -> Result<()> {
     let selected = selections[selection];
 
     if selected == YesNo::Yes {
-        test_nginx()?;
+        transaction.commit()?;
         reload_nginx()?;
     } else {
         info!("Skipping test and reload...");
@@ -1,12 +1,38 @@
+use crate::access_log::log_format_for;
 use crate::config::CONFIG;
-use crate::utils::{view_log_file, walk_folder, FileData};
-use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, FuzzySelect};
+use crate::error_log::ErrorEntry;
+use crate::layout::{discover_sites, find_site, Site};
+use crate::log_filter::Filter;
+use crate::log_reader::{by_age, log_files, open_log, rotated_from, rotated_set};
+use crate::pager::{follow_log_files, view_log_files};
+use crate::site_logs::{server_names, site_logs, LogKind, SiteLog};
+use crate::utils::{walk_folder, FileData};
+use anyhow::{anyhow, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, MultiSelect, Select};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A log and the logs rotated from it, e.g. `access.log`, `access.log.1` and
+/// `access.log.2.gz`.
+#[derive(Debug, Clone)]
+pub struct LogSet {
+    /// File name of the live log, which may have been rotated away.
+    pub name: String,
+    /// Newest first.
+    pub files: Vec<FileData>,
+}
+
+impl LogSet {
+    /// The log nginx currently writes to.
+    pub fn live(&self) -> Option<&FileData> {
+        self.files.iter().find(|x| x.file_name == self.name)
+    }
+}
 
 pub async fn get_site_logs() -> Result<Vec<FileData>> {
     let mut list: Vec<FileData> = vec![];
 
-    let available = walk_folder(&CONFIG.paths.logs).await?;
+    let available = walk_folder(&CONFIG.paths().logs).await?;
 
     for (_, file) in available {
         list.push(file)
@@ -17,23 +43,350 @@ pub async fn get_site_logs() -> Result<Vec<FileData>> {
     Ok(list)
 }
 
+/// Logs grouped by the file they were rotated from, sorted by name.
+pub async fn get_log_sets() -> Result<Vec<LogSet>> {
+    let mut sets: BTreeMap<String, Vec<FileData>> = BTreeMap::new();
+
+    for file in get_site_logs().await? {
+        let name = rotated_from(Path::new(&file.file_name));
+        let name = name.to_string_lossy().into_owned();
+
+        sets.entry(name).or_default().push(file);
+    }
+
+    Ok(sets
+        .into_iter()
+        .map(|(name, mut files)| {
+            files.sort_by(|a, b| by_age(Path::new(&a.file_path), Path::new(&b.file_path)));
+            LogSet { name, files }
+        })
+        .collect())
+}
+
+/// Picks a log set, then the whole set or one of its files. `None` when there are no
+/// logs.
+pub async fn pick_log_files() -> Result<Option<Vec<FileData>>> {
+    let sets = get_log_sets().await?;
+
+    if sets.is_empty() {
+        return Ok(None);
+    }
+
+    let selections: Vec<String> = sets
+        .iter()
+        .map(|x| match x.files.len() {
+            1 => x.name.clone(),
+            len => format!("{} ({len} files)", x.name),
+        })
+        .collect();
+
+    let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Pick log file")
+        .default(0)
+        .items(&selections[..])
+        .interact()?;
+
+    let set = &sets[selection];
+
+    if set.files.len() == 1 {
+        return Ok(Some(set.files.clone()));
+    }
+
+    let mut selections = vec![format!("All {} files, merged", set.files.len())];
+    selections.extend(set.files.iter().map(|x| x.file_name.clone()));
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Pick rotated log")
+        .default(0)
+        .items(&selections[..])
+        .interact()?;
+
+    match selection {
+        0 => Ok(Some(set.files.clone())),
+        index => Ok(Some(vec![set.files[index - 1].clone()])),
+    }
+}
+
 pub async fn ng_view_logs() -> Result<()> {
-    let list: Vec<FileData> = get_site_logs().await?;
-    if !list.is_empty() {
-        let selections: &Vec<&String> = &list.iter().map(|x| &x.file_name).collect();
+    let sites = discover_sites().await?;
+
+    if !sites.is_empty() {
+        let mut selections: Vec<&str> = sites.iter().map(|x| x.name.as_str()).collect();
+        selections.push("Other logs...");
+
         let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Pick log file")
+            .with_prompt("Pick site")
             .default(0)
             .items(&selections[..])
-            .interact()
-            .unwrap();
+            .interact()?;
+
+        if let Some(site) = sites.get(selection) {
+            return ng_view_site_logs(site).await;
+        }
+    }
 
-        let selected_log = &list[selection];
+    if let Some(files) = pick_log_files().await? {
+        let filter = match any_error_log(&files) {
+            true => None,
+            false => ask_filter()?,
+        };
 
-        view_log_file(selected_log.file_name.clone()).await?;
+        view_log_files(&files, filter.as_ref()).await?;
     } else {
         info!("No sites found to view...");
     }
 
     Ok(())
 }
+
+/// Picks one of the logs `site` writes to.
+async fn ng_view_site_logs(site: &Site) -> Result<()> {
+    let logs = site_logs(site).await?;
+
+    if logs.is_empty() {
+        info!("{} doesn't write any logs...", site.name);
+        return Ok(());
+    }
+
+    let selections: Vec<String> = logs
+        .iter()
+        .map(|x| match x.shared {
+            true => format!("{:<6}  {} (shared)", x.kind, x.file_path.display()),
+            false => format!("{:<6}  {}", x.kind, x.file_path.display()),
+        })
+        .collect();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Pick log")
+        .default(0)
+        .items(&selections[..])
+        .interact()?;
+
+    let log = &logs[selection];
+    let mut files = vec![log.file_path.clone()];
+    let rotated = rotated_set(&log.file_path).unwrap_or_default();
+
+    if rotated.len() > 1
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Include {} rotated log(s)?", rotated.len() - 1))
+            .default(false)
+            .interact()?
+    {
+        files = rotated;
+    }
+
+    // Error logs aren't access logs, access log filters hide all of their lines.
+    let mut filter = None;
+
+    if log.kind == LogKind::Access {
+        if log.shared
+            && Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Only show requests to {}?", site.name))
+                .default(true)
+                .interact()?
+        {
+            filter = host_filter(site, &files)?;
+        }
+
+        filter = both(filter, ask_filter()?);
+    }
+
+    let files: Vec<FileData> = files.iter().map(|x| log_file_data(x)).collect();
+
+    view_log_files(&files, filter.as_ref()).await
+}
+
+/// Requests to the server names of `site`, `None` when one of `files` doesn't log the
+/// host.
+fn host_filter(
+    site: &Site,
+    files: &[PathBuf],
+) -> Result<Option<Filter>> {
+    for file in files {
+        let format = log_format_for(file);
+
+        if !["host", "http_host", "server_name"]
+            .iter()
+            .any(|x| format.logs(x))
+        {
+            warn!(
+                "{} doesn't log $host, showing requests of every site...",
+                file.display()
+            );
+            return Ok(None);
+        }
+    }
+
+    let filter = Filter::hosts(&server_names(site)?)?;
+
+    if filter.is_none() {
+        warn!(
+            "{} has no server name to filter by, showing requests of every site...",
+            site.name
+        );
+    }
+
+    Ok(filter)
+}
+
+fn both(
+    a: Option<Filter>,
+    b: Option<Filter>,
+) -> Option<Filter> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.and(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Whether the newest line of the log at `file_path` is an error log entry.
+fn is_error_log(file_path: &Path) -> bool {
+    open_log(file_path)
+        .ok()
+        .and_then(|mut lines| lines.next())
+        .and_then(Result::ok)
+        .is_some_and(|line| ErrorEntry::parse(&line).is_some())
+}
+
+/// Whether one of `files` is an error log, which an access log filter would empty.
+fn any_error_log(files: &[FileData]) -> bool {
+    files.iter().any(|x| is_error_log(Path::new(&x.file_path)))
+}
+
+/// `FileData` of a log, named relative to the logs directory.
+fn log_file_data(file_path: &Path) -> FileData {
+    FileData {
+        file_name: file_path
+            .strip_prefix(&CONFIG.paths().logs)
+            .unwrap_or(file_path)
+            .to_string_lossy()
+            .into_owned(),
+        file_path: file_path.to_string_lossy().into_owned(),
+        is_symlink: false,
+    }
+}
+
+pub async fn ng_follow_logs() -> Result<()> {
+    // Only live logs are written to.
+    let list: Vec<FileData> = get_log_sets()
+        .await?
+        .iter()
+        .filter_map(|x| x.live().cloned())
+        .collect();
+
+    if !list.is_empty() {
+        let selections: &Vec<&String> = &list.iter().map(|x| &x.file_name).collect();
+        let selections = MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt("Pick log file(s)")
+            .items(&selections[..])
+            .interact()?;
+
+        let selected_logs: Vec<FileData> =
+            selections.into_iter().map(|x| list[x].clone()).collect();
+
+        if !selected_logs.is_empty() {
+            let filter = match any_error_log(&selected_logs) {
+                true => None,
+                false => ask_filter()?,
+            };
+
+            follow_log_files(&selected_logs, filter.as_ref()).await?;
+        }
+    } else {
+        info!("No sites found to view...");
+    }
+
+    Ok(())
+}
+
+pub async fn find_log(file_name: &str) -> Result<FileData> {
+    get_site_logs()
+        .await?
+        .into_iter()
+        .find(|x| x.file_name == file_name)
+        .ok_or_else(|| anyhow!("{file_name} not found in {}", CONFIG.paths().logs))
+}
+
+/// Asks until the filter is valid, `None` when left empty.
+pub fn ask_filter() -> Result<Option<Filter>> {
+    loop {
+        let filter: String = Input::with_theme(&ColorfulTheme::default())
+            .with_prompt("Filter (e.g. status>=500 and path~^/api), empty for all")
+            .allow_empty(true)
+            .interact_text()?;
+
+        if filter.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match Filter::parse(&filter) {
+            Ok(filter) => return Ok(Some(filter)),
+            Err(err) => error!("{err:#}"),
+        }
+    }
+}
+
+pub async fn view_logs(
+    files: Vec<String>,
+    site: Option<String>,
+    error: bool,
+    by_host: bool,
+    follow: bool,
+    rotated: bool,
+    filter: Option<String>,
+) -> Result<()> {
+    if error && filter.is_some() {
+        return Err(anyhow!(
+            "Filters only apply to access logs, drop --filter to view error logs"
+        ));
+    }
+
+    let mut filter = filter.as_deref().map(Filter::parse).transpose()?;
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for file in &files {
+        paths.push(PathBuf::from(find_log(file).await?.file_path));
+    }
+
+    if let Some(site) = site {
+        let site = find_site(&site).await?;
+        let kind = match error {
+            true => LogKind::Error,
+            false => LogKind::Access,
+        };
+
+        let logs: Vec<SiteLog> = site_logs(&site)
+            .await?
+            .into_iter()
+            .filter(|x| x.kind == kind)
+            .collect();
+
+        if logs.is_empty() {
+            return Err(anyhow!("{} doesn't write any {kind} logs", site.name));
+        }
+
+        paths = logs.iter().map(|x| x.file_path.clone()).collect();
+
+        if by_host && kind == LogKind::Access && logs.iter().any(|x| x.shared) {
+            filter = both(host_filter(&site, &paths)?, filter);
+        }
+    }
+
+    if !follow {
+        paths = log_files(&paths, rotated)?;
+    }
+
+    if let Some(path) = paths.iter().find(|x| filter.is_some() && is_error_log(x)) {
+        return Err(anyhow!(
+            "{} is an error log, filters only apply to access logs",
+            path.display()
+        ));
+    }
+
+    let logs: Vec<FileData> = paths.iter().map(|x| log_file_data(x)).collect();
+
+    match follow {
+        true => follow_log_files(&logs, filter.as_ref()).await,
+        false => view_log_files(&logs, filter.as_ref()).await,
+    }
+}
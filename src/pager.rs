@@ -0,0 +1,329 @@
+use crate::access_log::{log_format_for, LogFormat};
+use crate::config::CONFIG;
+use crate::geoip::GeoIp;
+use crate::log_filter::{line_filter, Filter};
+use crate::log_ignore::Ignore;
+use crate::log_reader::{by_age, is_gzip, newest_first, LogFollower, LogLines, ReverseLines};
+use crate::ng_export::ask_export;
+use crate::utils::FileData;
+use anyhow::Result;
+use minus::input::{generate_default_bindings, HashedEventRegister, InputEvent};
+use minus::{dynamic_paging, ExitStrategy, LineNumbers, MinusError, Pager};
+use std::io;
+use std::iter::once;
+use std::mem::take;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::spawn_blocking;
+use tokio::time::sleep;
+
+const PAGER_BATCH_LINES: usize = 1000;
+const FOLLOW_CONTEXT_LINES: usize = 10;
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+/// Shows and hides the lines of the ignore rules in the log viewer.
+const SHOW_IGNORED_KEY: &str = "i";
+/// Closes the log viewer to export the requests it shows.
+const EXPORT_KEY: &str = "e";
+
+/// Pages `files` as one stream, newest first. Rotated logs don't overlap, so ordering the
+/// files by age merges them chronologically.
+pub async fn view_log_files(
+    files: &[FileData],
+    filter: Option<&Filter>,
+) -> Result<()> {
+    let mut files: Vec<&FileData> = files
+        .iter()
+        .filter(|x| Path::new(&x.file_path).exists())
+        .collect();
+
+    if files.is_empty() {
+        info!("File not found.");
+
+        return Ok(());
+    }
+
+    files.sort_by(|a, b| by_age(Path::new(&a.file_path), Path::new(&b.file_path)));
+
+    let mut prompt = match files.as_slice() {
+        [file] => file.file_name.clone(),
+        [newest, rest @ ..] => format!("{} and {} older log(s)", newest.file_name, rest.len()),
+        [] => String::new(),
+    };
+
+    // Their newest lines are at the end, they show up once the whole file is decoded.
+    if files
+        .iter()
+        .any(|x| is_gzip(Path::new(&x.file_path)).unwrap_or(false))
+    {
+        prompt.push_str(", compressed logs are read in full first");
+    }
+
+    let ignore = Ignore::from_config()?;
+    let files: Vec<PathBuf> = files.iter().map(|x| PathBuf::from(&x.file_path)).collect();
+    let filter = filter.cloned();
+    let (export_files, export_filter) = (files.clone(), filter.clone());
+
+    let geoip = GeoIp::from_config()?.map(Arc::new);
+
+    let lines = move |show_ignored: bool| -> LogLines {
+        let filter = filter.clone();
+        let ignore = (!show_ignored).then(|| ignore.clone());
+        let geoip = geoip.clone();
+
+        Box::new(files.clone().into_iter().flat_map(move |file_path| {
+            let keep = line_filter(&file_path, filter.as_ref(), ignore.as_ref());
+            let lines = newest_first(&file_path, keep, CONFIG.max_log_lines)
+                .unwrap_or_else(|err| Box::new(once(Err(io::Error::other(err)))));
+
+            match geoip.clone() {
+                Some(geoip) => {
+                    let format = log_format_for(&file_path);
+                    Box::new(lines.map(move |x| x.map(|line| geoip.annotate(&format, line))))
+                }
+                None => lines,
+            }
+        }))
+    };
+
+    if log_pager(lines, &prompt).await? {
+        ask_export(&export_files, export_filter.as_ref())?;
+    }
+
+    Ok(())
+}
+
+/// Pages the lines appended to `files` until the pager is closed, lines are prefixed
+/// with their file name when following more than one.
+pub async fn follow_log_files(
+    files: &[FileData],
+    filter: Option<&Filter>,
+) -> Result<()> {
+    let ignore = Ignore::from_config()?;
+    let geoip = GeoIp::from_config()?;
+    let names: Vec<&str> = files.iter().map(|x| x.file_name.as_str()).collect();
+    let pager = new_pager(&format!("{} (following, G for newest)", names.join(", ")))?;
+
+    let mut followers = vec![];
+
+    for file in files {
+        let file_path = Path::new(&file.file_path);
+        let prefix = if files.len() > 1 {
+            format!("[{}] ", file.file_name)
+        } else {
+            String::new()
+        };
+
+        let keep = line_filter(file_path, filter, Some(&ignore));
+        let format = log_format_for(file_path);
+
+        // Some context, like `tail -f`.
+        let mut recent: Vec<String> = ReverseLines::open(file_path)?
+            .filter(|x| x.as_ref().map_or(true, |line| keep(line)))
+            .take(FOLLOW_CONTEXT_LINES)
+            .collect::<std::io::Result<_>>()?;
+        recent.reverse();
+
+        push_prefixed(
+            &pager,
+            &prefix,
+            with_ip_info(geoip.as_ref(), &format, recent),
+        )?;
+        followers.push((prefix, keep, format, LogFollower::open(file_path)));
+    }
+
+    let paging = spawn_blocking({
+        let pager = pager.clone();
+        move || dynamic_paging(pager)
+    });
+
+    while !paging.is_finished() {
+        for (prefix, keep, format, follower) in &mut followers {
+            let lines = follower.read_new()?;
+            let lines = lines.into_iter().filter(|x| keep(x)).collect();
+            let lines = with_ip_info(geoip.as_ref(), format, lines);
+
+            // Fails once the pager is closed.
+            if push_prefixed(&pager, prefix, lines).is_err() {
+                break;
+            }
+        }
+
+        sleep(FOLLOW_INTERVAL).await;
+    }
+
+    paging.await??;
+
+    Ok(())
+}
+
+/// Streams `lines` into the pager while it is open, so only the lines paged so far
+/// are kept in memory, at most `max_log_lines` of them.
+pub async fn cli_pager(
+    lines: LogLines,
+    prompt: &str,
+) -> Result<()> {
+    let pager = new_pager(prompt)?;
+
+    let paging = spawn_blocking({
+        let pager = pager.clone();
+        move || dynamic_paging(pager)
+    });
+
+    spawn_blocking(move || push_lines(&pager, lines, || false)).await??;
+    paging.await??;
+
+    Ok(())
+}
+
+/// Pages `lines(false)` like `cli_pager`, `SHOW_IGNORED_KEY` switches to `lines(true)`,
+/// which includes the ignored lines, and back. Returns whether the pager was closed with
+/// `EXPORT_KEY`.
+async fn log_pager(
+    lines: impl Fn(bool) -> LogLines + Send + 'static,
+    prompt: &str,
+) -> Result<bool> {
+    let keys =
+        |action: &str| format!("({SHOW_IGNORED_KEY} {action} ignored, {EXPORT_KEY} exports)");
+    let pager = new_pager(&format!("{prompt} {}", keys("shows")))?;
+    let (toggle, toggled) = channel();
+    let export = Arc::new(AtomicBool::new(false));
+
+    let mut input = HashedEventRegister::default();
+    generate_default_bindings(&mut input);
+    input.add_key_events(&[SHOW_IGNORED_KEY], move |_, _| {
+        let _ = toggle.send(());
+        InputEvent::Ignore
+    });
+    input.add_key_events(&[EXPORT_KEY], {
+        let export = export.clone();
+        move |_, _| {
+            export.store(true, Ordering::Relaxed);
+            InputEvent::Exit
+        }
+    });
+    pager.set_input_classifier(Box::new(input))?;
+
+    let paging = spawn_blocking({
+        let pager = pager.clone();
+        move || dynamic_paging(pager)
+    });
+
+    let prompt = prompt.to_string();
+
+    spawn_blocking(move || -> Result<()> {
+        let mut show_ignored = false;
+
+        loop {
+            let toggled_early =
+                push_lines(&pager, lines(show_ignored), || toggled.try_recv().is_ok())?;
+
+            // The key's sender is dropped with the pager once it's closed.
+            if !toggled_early && toggled.recv().is_err() {
+                return Ok(());
+            }
+
+            show_ignored = !show_ignored;
+            let action = match show_ignored {
+                true => "hides",
+                false => "shows",
+            };
+
+            if pager.set_text("").is_err()
+                || pager
+                    .set_prompt(format!("{prompt} {}", keys(action)))
+                    .is_err()
+            {
+                return Ok(());
+            }
+        }
+    })
+    .await??;
+    paging.await??;
+
+    Ok(export.load(Ordering::Relaxed))
+}
+
+/// Pushes `lines` until they run out or `interrupt` returns true, which is checked
+/// before each line. Returns whether it was interrupted.
+fn push_lines(
+    pager: &Pager,
+    lines: LogLines,
+    interrupt: impl Fn() -> bool,
+) -> Result<bool> {
+    let mut count = 0;
+    let mut batch = String::new();
+
+    for line in lines {
+        if interrupt() {
+            return Ok(true);
+        }
+
+        let line = line?;
+
+        if count == CONFIG.max_log_lines {
+            let _ = pager.send_message(format!("Showing the newest {count} lines"));
+            break;
+        }
+
+        batch.push_str(&line);
+        batch.push('\n');
+        count += 1;
+
+        // minus recounts every line on each push, lines are pushed in batches to keep
+        // that cheap. Fails once the pager is closed, nobody is left to read the rest.
+        if count % PAGER_BATCH_LINES == 0 && pager.push_str(take(&mut batch)).is_err() {
+            return Ok(false);
+        }
+    }
+
+    if !batch.is_empty() {
+        let _ = pager.push_str(batch);
+    }
+
+    Ok(false)
+}
+
+/// Prefixes lines with the country and network of their client, with a GeoIP database.
+fn with_ip_info(
+    geoip: Option<&GeoIp>,
+    format: &LogFormat,
+    lines: Vec<String>,
+) -> Vec<String> {
+    match geoip {
+        Some(geoip) => lines
+            .into_iter()
+            .map(|line| geoip.annotate(format, line))
+            .collect(),
+        None => lines,
+    }
+}
+
+fn push_prefixed(
+    pager: &Pager,
+    prefix: &str,
+    lines: Vec<String>,
+) -> Result<(), MinusError> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let text: String = lines
+        .iter()
+        .map(|line| format!("{prefix}{line}\n"))
+        .collect();
+
+    pager.push_str(text)
+}
+
+fn new_pager(prompt: &str) -> Result<Pager> {
+    let pager = Pager::new();
+
+    pager.set_exit_strategy(ExitStrategy::PagerQuit)?;
+    pager.set_line_numbers(LineNumbers::AlwaysOn)?;
+    pager.set_prompt(prompt)?;
+
+    Ok(pager)
+}
@@ -0,0 +1,571 @@
+use crate::utils::nginx_conf_file;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Deepest include chain followed, guards against include cycles.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// 1-based position in a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum Node {
+    Directive(Directive),
+    Comment { text: String, span: Span },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Directive {
+    pub name: String,
+    /// Arguments with quotes removed.
+    pub args: Vec<String>,
+    /// `Some` for block directives such as `server { ... }`.
+    pub block: Option<Vec<Node>>,
+    pub span: Span,
+    /// Files matched by an `include`, filled by `parse_file_with_includes`.
+    pub includes: Vec<ConfigFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub nodes: Vec<Node>,
+}
+
+impl Directive {
+    pub fn arg(
+        &self,
+        index: usize,
+    ) -> Option<&str> {
+        self.args.get(index).map(|x| x.as_str())
+    }
+
+    /// Directives of the block, with included files expanded in place.
+    pub fn children(&self) -> Vec<&Directive> {
+        match &self.block {
+            Some(nodes) => expand(nodes),
+            None => vec![],
+        }
+    }
+
+    /// Direct children named `name`.
+    pub fn find(
+        &self,
+        name: &str,
+    ) -> Vec<&Directive> {
+        self.children()
+            .into_iter()
+            .filter(|x| x.name == name)
+            .collect()
+    }
+
+    /// Every directive named `name` inside the block, at any depth.
+    pub fn find_all(
+        &self,
+        name: &str,
+    ) -> Vec<&Directive> {
+        find_all(self.children(), name)
+    }
+}
+
+impl ConfigFile {
+    /// Top level directives, with included files expanded in place.
+    pub fn directives(&self) -> Vec<&Directive> {
+        expand(&self.nodes)
+    }
+
+    /// Every directive named `name`, at any depth.
+    pub fn find_all(
+        &self,
+        name: &str,
+    ) -> Vec<&Directive> {
+        find_all(self.directives(), name)
+    }
+
+    /// `server` blocks, either top level (sites) or inside `http` (nginx.conf).
+    pub fn servers(&self) -> Vec<&Directive> {
+        self.find_all("server")
+            .into_iter()
+            .filter(|x| x.block.is_some())
+            .collect()
+    }
+}
+
+/// Depth first, in file order.
+fn find_all<'a>(
+    directives: Vec<&'a Directive>,
+    name: &str,
+) -> Vec<&'a Directive> {
+    let mut found: Vec<&Directive> = vec![];
+    let mut stack = directives;
+
+    stack.reverse();
+
+    while let Some(directive) = stack.pop() {
+        if directive.name == name {
+            found.push(directive);
+        }
+
+        let mut children = directive.children();
+        children.reverse();
+        stack.extend(children);
+    }
+
+    found
+}
+
+fn expand(nodes: &[Node]) -> Vec<&Directive> {
+    let mut directives: Vec<&Directive> = vec![];
+
+    for node in nodes {
+        let Node::Directive(directive) = node else {
+            continue;
+        };
+
+        if directive.name == "include" && !directive.includes.is_empty() {
+            for file in &directive.includes {
+                directives.extend(expand(&file.nodes));
+            }
+        } else {
+            directives.push(directive);
+        }
+    }
+
+    directives
+}
+
+/// Parses a single file, `include` directives are left unresolved.
+pub fn parse_file(path: &Path) -> Result<ConfigFile> {
+    let source = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let nodes = parse(&source).map_err(|err| anyhow!("{}:{err}", path.display()))?;
+
+    Ok(ConfigFile {
+        path: path.to_path_buf(),
+        nodes,
+    })
+}
+
+/// Parses a file and every file it includes. Relative include patterns are resolved
+/// against the directory of the configured nginx.conf, like nginx does.
+pub fn parse_file_with_includes(path: &Path) -> Result<ConfigFile> {
+    let mut file = parse_file(path)?;
+
+    resolve_includes(&mut file.nodes, 0)?;
+
+    Ok(file)
+}
+
+fn resolve_includes(
+    nodes: &mut [Node],
+    depth: usize,
+) -> Result<()> {
+    for node in nodes {
+        let Node::Directive(directive) = node else {
+            continue;
+        };
+
+        if let Some(block) = &mut directive.block {
+            resolve_includes(block, depth)?;
+        }
+
+        if directive.name != "include" {
+            continue;
+        }
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(anyhow!("Includes nested too deeply at {}", directive.span));
+        }
+
+        let Some(pattern) = directive.args.first() else {
+            continue;
+        };
+
+        for path in include_paths(pattern)? {
+            let mut file = parse_file(&path)?;
+            resolve_includes(&mut file.nodes, depth + 1)?;
+            directive.includes.push(file);
+        }
+    }
+
+    Ok(())
+}
+
+fn include_paths(pattern: &str) -> Result<Vec<PathBuf>> {
+    let conf_file = nginx_conf_file();
+    let conf_dir = conf_file.parent().unwrap_or(Path::new("/"));
+    let pattern = conf_dir.join(pattern);
+    let mut paths: Vec<PathBuf> = vec![];
+
+    for entry in glob::glob(&pattern.to_string_lossy())? {
+        let path = entry?;
+
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    OpenBrace,
+    CloseBrace,
+    Semicolon,
+    Comment(String),
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let char = self.chars.next()?;
+
+        if char == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(char)
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, Span)>, String> {
+        while self.chars.peek().is_some_and(|x| x.is_whitespace()) {
+            self.bump();
+        }
+
+        let span = self.span();
+
+        let Some(&char) = self.chars.peek() else {
+            return Ok(None);
+        };
+
+        let token = match char {
+            '{' => {
+                self.bump();
+                Token::OpenBrace
+            }
+            '}' => {
+                self.bump();
+                Token::CloseBrace
+            }
+            ';' => {
+                self.bump();
+                Token::Semicolon
+            }
+            '#' => {
+                self.bump();
+                let mut text = String::new();
+
+                while let Some(&char) = self.chars.peek() {
+                    if char == '\n' {
+                        break;
+                    }
+
+                    text.push(char);
+                    self.bump();
+                }
+
+                Token::Comment(text.trim().to_string())
+            }
+            '"' | '\'' => {
+                self.bump();
+                Token::Word(self.quoted(char, span)?)
+            }
+            _ => Token::Word(self.word()),
+        };
+
+        Ok(Some((token, span)))
+    }
+
+    fn quoted(
+        &mut self,
+        quote: char,
+        span: Span,
+    ) -> Result<String, String> {
+        let mut value = String::new();
+
+        loop {
+            match self.bump() {
+                Some('\\') => match self.bump() {
+                    Some(char) if char == quote || char == '\\' => value.push(char),
+                    Some(char) => {
+                        value.push('\\');
+                        value.push(char);
+                    }
+                    None => break,
+                },
+                Some(char) if char == quote => return Ok(value),
+                Some(char) => value.push(char),
+                None => break,
+            }
+        }
+
+        Err(format!("{span}: unterminated string"))
+    }
+
+    fn word(&mut self) -> String {
+        let mut value = String::new();
+
+        while let Some(&char) = self.chars.peek() {
+            match char {
+                ';' | '{' | '}' => break,
+                char if char.is_whitespace() => break,
+                '\\' => {
+                    self.bump();
+                    value.push('\\');
+
+                    if let Some(char) = self.bump() {
+                        value.push(char);
+                    }
+                }
+                '$' => {
+                    self.bump();
+                    value.push('$');
+
+                    // `${var}` keeps its braces
+                    if self.chars.peek() == Some(&'{') {
+                        while let Some(char) = self.bump() {
+                            value.push(char);
+
+                            if char == '}' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    value.push(char);
+                    self.bump();
+                }
+            }
+        }
+
+        value
+    }
+}
+
+/// Parses nginx config source into nodes.
+pub fn parse(source: &str) -> Result<Vec<Node>, String> {
+    let mut lexer = Lexer::new(source);
+    let nodes = parse_block(&mut lexer, None)?;
+
+    Ok(nodes)
+}
+
+fn parse_block(
+    lexer: &mut Lexer,
+    opened_at: Option<Span>,
+) -> Result<Vec<Node>, String> {
+    let mut nodes: Vec<Node> = vec![];
+
+    loop {
+        let Some((token, span)) = lexer.next_token()? else {
+            return match opened_at {
+                Some(opened_at) => Err(format!("{opened_at}: unclosed block")),
+                None => Ok(nodes),
+            };
+        };
+
+        match token {
+            Token::Comment(text) => nodes.push(Node::Comment { text, span }),
+            Token::CloseBrace => {
+                return match opened_at {
+                    Some(_) => Ok(nodes),
+                    None => Err(format!("{span}: unexpected \"}}\"")),
+                };
+            }
+            Token::OpenBrace | Token::Semicolon => {
+                return Err(format!("{span}: expected directive name"));
+            }
+            Token::Word(name) => {
+                let directive = parse_directive(lexer, name, span)?;
+                nodes.push(Node::Directive(directive));
+            }
+        }
+    }
+}
+
+fn parse_directive(
+    lexer: &mut Lexer,
+    name: String,
+    span: Span,
+) -> Result<Directive, String> {
+    let mut args: Vec<String> = vec![];
+
+    loop {
+        let Some((token, token_span)) = lexer.next_token()? else {
+            return Err(format!("{span}: unexpected end of file in \"{name}\""));
+        };
+
+        match token {
+            Token::Word(arg) => args.push(arg),
+            // comments between arguments are dropped
+            Token::Comment(_) => {}
+            Token::Semicolon => {
+                return Ok(Directive {
+                    name,
+                    args,
+                    block: None,
+                    span,
+                    includes: vec![],
+                });
+            }
+            Token::OpenBrace => {
+                let block = parse_block(lexer, Some(token_span))?;
+
+                return Ok(Directive {
+                    name,
+                    args,
+                    block: Some(block),
+                    span,
+                    includes: vec![],
+                });
+            }
+            Token::CloseBrace => {
+                return Err(format!("{token_span}: unexpected \"}}\" in \"{name}\""));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directives(source: &str) -> Vec<Directive> {
+        parse(source)
+            .unwrap()
+            .into_iter()
+            .filter_map(|x| match x {
+                Node::Directive(directive) => Some(directive),
+                Node::Comment { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_blocks_and_spans() {
+        let nodes = directives("http {\n    server {\n        listen 80;\n    }\n}\n");
+
+        let server = &nodes[0].children()[0];
+        let listen = &server.children()[0];
+
+        assert_eq!(server.name, "server");
+        assert_eq!(listen.args, ["80"]);
+        assert_eq!(listen.span, Span { line: 3, column: 9 });
+    }
+
+    #[test]
+    fn unquotes_arguments() {
+        let nodes = directives(r#"add_header X-Test "a \"b\" c" 'd;e' always;"#);
+
+        assert_eq!(nodes[0].args, ["X-Test", r#"a "b" c"#, "d;e", "always"]);
+    }
+
+    #[test]
+    fn keeps_variables_and_escapes_in_words() {
+        let nodes = directives(r"rewrite ^/(.*)\.php$ /${1}x last;");
+
+        assert_eq!(nodes[0].args, [r"^/(.*)\.php$", "/${1}x", "last"]);
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let nodes = parse("# first\nlisten 80; # trailing\n").unwrap();
+
+        assert!(matches!(&nodes[0], Node::Comment { text, .. } if text == "first"));
+        assert!(matches!(&nodes[2], Node::Comment { text, .. } if text == "trailing"));
+    }
+
+    #[test]
+    fn reports_errors_with_positions() {
+        assert_eq!(parse("server {\n").unwrap_err(), "1:8: unclosed block");
+        assert_eq!(parse("}").unwrap_err(), "1:1: unexpected \"}\"");
+        assert_eq!(
+            parse("listen 80").unwrap_err(),
+            "1:1: unexpected end of file in \"listen\""
+        );
+        assert_eq!(
+            parse("root \"/srv;\n").unwrap_err(),
+            "1:6: unterminated string"
+        );
+    }
+
+    #[test]
+    fn expands_includes_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("nginx.conf");
+        let sites = dir.path().join("sites");
+
+        fs::create_dir(&sites).unwrap();
+        fs::write(sites.join("b.conf"), "server { server_name b; }").unwrap();
+        fs::write(sites.join("a.conf"), "server { server_name a; }").unwrap();
+        fs::write(
+            &main,
+            format!("http {{ include {}/*.conf; }}", sites.display()),
+        )
+        .unwrap();
+
+        let config = parse_file_with_includes(&main).unwrap();
+        let names: Vec<&str> = config
+            .servers()
+            .iter()
+            .filter_map(|x| x.find("server_name")[0].arg(0))
+            .collect();
+
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn stops_include_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("nginx.conf");
+
+        fs::write(&main, format!("include {};", main.display())).unwrap();
+
+        let err = parse_file_with_includes(&main).unwrap_err();
+
+        assert!(err.to_string().contains("nested too deeply"), "{err}");
+    }
+}
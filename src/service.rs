@@ -0,0 +1,232 @@
+use crate::parser::parse_file_with_includes;
+use crate::utils::{
+    get_command_path, nginx_built_with, nginx_command, nginx_conf_file, nginx_prefix,
+};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Where nginx writes its pid when neither nginx.conf nor its build set one, relative
+/// to the prefix.
+const DEFAULT_PID_FILE: &str = "logs/nginx.pid";
+/// How long a started nginx has to daemonize or fail, in checks 200ms apart.
+const START_CHECKS: usize = 25;
+
+/// How nginx is reloaded and restarted, selected with `manager` in `[service]`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "manager", rename_all = "lowercase")]
+pub enum ServiceManager {
+    /// `systemctl reload <unit>`
+    Systemd {
+        #[serde(default = "default_service_name")]
+        unit: String,
+    },
+    /// `nginx -s reload`, or a signal to the pid in `pid_file` when set.
+    Signal {
+        #[serde(default)]
+        pid_file: Option<String>,
+    },
+    /// `rc-service <service> reload`
+    Openrc {
+        #[serde(default = "default_service_name")]
+        service: String,
+    },
+    /// User supplied commands, e.g. `docker exec web nginx -s reload`.
+    Command {
+        reload: String,
+        #[serde(default)]
+        restart: Option<String>,
+    },
+}
+
+impl Default for ServiceManager {
+    fn default() -> Self {
+        Self::Systemd {
+            unit: default_service_name(),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "nginx".into()
+}
+
+impl ServiceManager {
+    pub fn reload(&self) -> Result<()> {
+        match self {
+            Self::Systemd { unit } => run(systemctl("reload", unit)?),
+            Self::Signal { pid_file: None } => run(nginx_signal("reload")?),
+            Self::Signal {
+                pid_file: Some(pid_file),
+            } => run(kill("HUP", pid_file)?),
+            Self::Openrc { service } => run(rc_service("reload", service)?),
+            Self::Command { reload, .. } => run(user_command(reload)?),
+        }
+    }
+
+    pub fn restart(&self) -> Result<()> {
+        match self {
+            Self::Systemd { unit } => run(systemctl("restart", unit)?),
+            Self::Signal { pid_file } => {
+                match pid_file {
+                    Some(pid_file) => run(kill("QUIT", pid_file)?)?,
+                    None => run(nginx_signal("quit")?)?,
+                }
+
+                let pid_file = match pid_file {
+                    Some(pid_file) => PathBuf::from(pid_file),
+                    None => nginx_pid_file(),
+                };
+
+                wait_for_exit(&pid_file)?;
+                start(nginx_command()?)
+            }
+            Self::Openrc { service } => run(rc_service("restart", service)?),
+            Self::Command { restart, .. } => {
+                let restart = restart
+                    .as_ref()
+                    .context("No restart command set in [service]")?;
+
+                run(user_command(restart)?)
+            }
+        }
+    }
+}
+
+fn systemctl(
+    action: &str,
+    unit: &str,
+) -> Result<Command> {
+    let mut command = Command::new(get_command_path("systemctl")?);
+    command.arg(action).arg(unit);
+
+    Ok(command)
+}
+
+fn rc_service(
+    action: &str,
+    service: &str,
+) -> Result<Command> {
+    let mut command = Command::new(get_command_path("rc-service")?);
+    command.arg(service).arg(action);
+
+    Ok(command)
+}
+
+fn nginx_signal(signal: &str) -> Result<Command> {
+    let mut command = nginx_command()?;
+    command.arg("-s").arg(signal);
+
+    Ok(command)
+}
+
+fn kill(
+    signal: &str,
+    pid_file: &str,
+) -> Result<Command> {
+    let mut command = Command::new(get_command_path("kill")?);
+    command.arg(format!("-{signal}")).arg(read_pid(pid_file)?);
+
+    Ok(command)
+}
+
+fn user_command(command_line: &str) -> Result<Command> {
+    let words = shell_words::split(command_line)
+        .context(format!("Invalid service command: {command_line}"))?;
+    let (program, args) = words.split_first().context("Service command is empty")?;
+
+    let mut command = Command::new(get_command_path(program)?);
+    command.args(args);
+
+    Ok(command)
+}
+
+fn read_pid(pid_file: &str) -> Result<String> {
+    let pid = fs::read_to_string(pid_file).context(format!("Failed to read {pid_file}"))?;
+
+    Ok(pid.trim().to_string())
+}
+
+/// The `pid` of nginx.conf, the `--pid-path` nginx was built with or its default,
+/// relative ones are relative to the prefix.
+fn nginx_pid_file() -> PathBuf {
+    let configured = parse_file_with_includes(&nginx_conf_file())
+        .ok()
+        .and_then(|config| {
+            config
+                .directives()
+                .iter()
+                .find(|x| x.name == "pid")
+                .and_then(|x| x.arg(0))
+                .map(String::from)
+        });
+
+    let pid_file = configured
+        .or_else(|| nginx_built_with("--pid-path"))
+        .unwrap_or_else(|| DEFAULT_PID_FILE.into());
+
+    nginx_prefix().join(pid_file)
+}
+
+/// Waits for the master process to exit after `quit`, nginx can't start while it runs.
+fn wait_for_exit(pid_file: &Path) -> Result<()> {
+    for _ in 0..50 {
+        let is_running = match fs::read_to_string(pid_file) {
+            Ok(pid) => Path::new("/proc").join(pid.trim()).exists(),
+            Err(_) => false,
+        };
+
+        if !is_running {
+            return Ok(());
+        }
+
+        sleep(Duration::from_millis(200));
+    }
+
+    Err(anyhow!("Nginx did not stop in time"))
+}
+
+/// Starts nginx without waiting for it to exit, with `daemon off;` it never does and
+/// is left running.
+fn start(mut command: Command) -> Result<()> {
+    let mut stderr = tempfile::tempfile()?;
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(stderr.try_clone()?)
+        .spawn()?;
+
+    for _ in 0..START_CHECKS {
+        if let Some(status) = child.try_wait()? {
+            if status.success() {
+                return Ok(());
+            }
+
+            let mut err = String::new();
+            stderr.rewind()?;
+            stderr.read_to_string(&mut err)?;
+
+            return Err(anyhow!(err));
+        }
+
+        sleep(Duration::from_millis(200));
+    }
+
+    Ok(())
+}
+
+fn run(mut command: Command) -> Result<()> {
+    let output = command.output()?;
+
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(anyhow!(err));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use crate::config::CONFIG;
+use crate::layout::{discover_sites, Layout};
+use crate::parser::{parse_file_with_includes, Directive};
+use crate::utils::walk_sites_folder;
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// nginx's `listen` when a server has none (as root).
+const DEFAULT_PORT: u16 = 80;
+
+/// A site file and what its `server` blocks serve.
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteSummary {
+    pub name: String,
+    pub file_path: String,
+    pub enabled: bool,
+    pub servers: Vec<ServerSummary>,
+    /// Set when the file could not be parsed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerSummary {
+    pub server_names: Vec<String>,
+    pub listen: Vec<Listen>,
+    pub roots: Vec<String>,
+    pub proxy_pass: Vec<String>,
+    pub access_logs: Vec<String>,
+    pub error_logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Listen {
+    /// `*`, `[::]`, an address or host name, or `unix:/path`.
+    pub address: String,
+    /// 0 for unix sockets.
+    pub port: u16,
+    pub ssl: bool,
+    pub default_server: bool,
+}
+
+impl Listen {
+    /// Parses the arguments of a `listen` directive, e.g. `[::]:443 ssl default_server`.
+    pub fn parse(args: &[String]) -> Option<Self> {
+        let (first, flags) = args.split_first()?;
+
+        let (address, port) = if first.starts_with("unix:") {
+            (first.clone(), Some(0))
+        } else if let Some(rest) = first.strip_prefix('[') {
+            let (address, port) = rest.split_once(']')?;
+            let port = port.strip_prefix(':').map(|x| x.parse().ok());
+
+            (format!("[{address}]"), port.unwrap_or(Some(DEFAULT_PORT)))
+        } else if let Some((address, port)) = first.rsplit_once(':') {
+            (address.to_string(), port.parse().ok())
+        } else if first.chars().all(|x| x.is_ascii_digit()) {
+            ("*".to_string(), first.parse().ok())
+        } else {
+            (first.clone(), Some(DEFAULT_PORT))
+        };
+
+        Some(Self {
+            address,
+            port: port?,
+            ssl: flags.iter().any(|x| x == "ssl"),
+            default_server: flags
+                .iter()
+                .any(|x| x == "default_server" || x == "default"),
+        })
+    }
+}
+
+impl Default for Listen {
+    fn default() -> Self {
+        Self {
+            address: "*".into(),
+            port: DEFAULT_PORT,
+            ssl: false,
+            default_server: false,
+        }
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        if self.port == 0 {
+            write!(f, "{}", self.address)?;
+        } else {
+            write!(f, "{}:{}", self.address, self.port)?;
+        }
+
+        if self.ssl {
+            write!(f, " ssl")?;
+        }
+
+        if self.default_server {
+            write!(f, " default")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ServerSummary {
+    pub fn from_directive(server: &Directive) -> Self {
+        let mut listen: Vec<Listen> = server
+            .find("listen")
+            .iter()
+            .filter_map(|x| Listen::parse(&x.args))
+            .collect();
+
+        if listen.is_empty() {
+            listen.push(Listen::default());
+        }
+
+        Self {
+            server_names: server
+                .find("server_name")
+                .iter()
+                .flat_map(|x| x.args.clone())
+                .collect(),
+            listen,
+            roots: first_args(server.find_all("root")),
+            proxy_pass: first_args(server.find_all("proxy_pass")),
+            access_logs: log_paths(server.find_all("access_log")),
+            error_logs: log_paths(server.find_all("error_log")),
+        }
+    }
+}
+
+fn first_args(directives: Vec<&Directive>) -> Vec<String> {
+    let mut values: Vec<String> = vec![];
+
+    for directive in directives {
+        if let Some(value) = directive.arg(0) {
+            if !values.iter().any(|x| x == value) {
+                values.push(value.to_string());
+            }
+        }
+    }
+
+    values
+}
+
+fn log_paths(directives: Vec<&Directive>) -> Vec<String> {
+    first_args(directives)
+        .into_iter()
+        .filter(|x| x != "off")
+        .collect()
+}
+
+impl SiteSummary {
+    pub fn parse(
+        name: impl Into<String>,
+        file_path: impl Into<String>,
+        enabled: bool,
+    ) -> Self {
+        let file_path = file_path.into();
+
+        let (servers, error) = match parse_file_with_includes(Path::new(&file_path)) {
+            Ok(config) => (
+                config
+                    .servers()
+                    .into_iter()
+                    .map(ServerSummary::from_directive)
+                    .collect(),
+                None,
+            ),
+            Err(err) => (vec![], Some(format!("{err:#}"))),
+        };
+
+        Self {
+            name: name.into(),
+            file_path,
+            enabled,
+            servers,
+            error,
+        }
+    }
+}
+
+/// Every site of the layout, plus files placed directly in sites-enabled.
+pub async fn list_sites() -> Result<Vec<SiteSummary>> {
+    let discovered = discover_sites().await?;
+
+    let mut sites: Vec<SiteSummary> = discovered
+        .iter()
+        .map(|x| SiteSummary::parse(&x.name, &x.file_path, x.enabled))
+        .collect();
+
+    if CONFIG.paths().layout == Layout::Debian {
+        let enabled = walk_sites_folder(&CONFIG.paths().sites_enabled).await?;
+
+        for (key, file) in &enabled {
+            if !file.is_symlink && !discovered.iter().any(|x| &x.name == key) {
+                sites.push(SiteSummary::parse(&file.file_name, &file.file_path, true));
+            }
+        }
+    }
+
+    sites.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(sites)
+}
@@ -0,0 +1,267 @@
+use crate::layout::{discover_sites, Site};
+use crate::parser::{parse_file_with_includes, ConfigFile, Directive};
+use crate::utils::{nginx_conf_file, nginx_prefix};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use strum::Display;
+
+/// Where nginx writes when no `access_log`/`error_log` is set anywhere, relative to the
+/// prefix.
+const DEFAULT_ACCESS_LOG: &str = "logs/access.log";
+const DEFAULT_ERROR_LOG: &str = "logs/error.log";
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum LogKind {
+    #[strum(serialize = "access")]
+    Access,
+    #[strum(serialize = "error")]
+    Error,
+}
+
+impl LogKind {
+    fn directive(self) -> &'static str {
+        match self {
+            Self::Access => "access_log",
+            Self::Error => "error_log",
+        }
+    }
+}
+
+/// A log a site writes to.
+#[derive(Debug, Clone)]
+pub struct SiteLog {
+    pub kind: LogKind,
+    pub file_path: PathBuf,
+    /// Inherited from nginx.conf or also set by another site, so it holds requests of
+    /// other sites too.
+    pub shared: bool,
+}
+
+/// Logs of `site`, in the order of its directives, access logs first.
+pub async fn site_logs(site: &Site) -> Result<Vec<SiteLog>> {
+    let main = parse_file_with_includes(Path::new(&nginx_conf_file())).ok();
+    let config = parse_file_with_includes(Path::new(&site.file_path))?;
+
+    // `nginx -V` is run once, not for every relative log path.
+    let prefix = nginx_prefix();
+    let mut logs = written_logs(&config, main.as_ref(), &prefix);
+
+    // Logs set by other sites, ignoring inherited ones, those are shared anyway.
+    let mut others: Vec<PathBuf> = vec![];
+
+    for other in discover_sites().await? {
+        if other.file_path == site.file_path {
+            continue;
+        }
+
+        if let Ok(config) = parse_file_with_includes(Path::new(&other.file_path)) {
+            others.extend(
+                written_logs(&config, None, &prefix)
+                    .into_iter()
+                    .map(|x| x.file_path),
+            );
+        }
+    }
+
+    for log in &mut logs {
+        log.shared |= others.contains(&log.file_path);
+    }
+
+    Ok(logs)
+}
+
+/// Server names of `site`.
+pub fn server_names(site: &Site) -> Result<Vec<String>> {
+    let config = parse_file_with_includes(Path::new(&site.file_path))?;
+    let mut names: Vec<String> = vec![];
+
+    for server in config.servers() {
+        for directive in server.find("server_name") {
+            for name in &directive.args {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+/// Logs the servers of `config` write to. Servers without their own `access_log` or
+/// `error_log` inherit the ones of `main` (nginx.conf), or nginx's defaults. Relative
+/// paths are resolved against `prefix`.
+fn written_logs(
+    config: &ConfigFile,
+    main: Option<&ConfigFile>,
+    prefix: &Path,
+) -> Vec<SiteLog> {
+    let mut logs: Vec<SiteLog> = vec![];
+
+    for kind in [LogKind::Access, LogKind::Error] {
+        for server in config.servers() {
+            let names: Vec<String> = server
+                .find("server_name")
+                .iter()
+                .flat_map(|x| x.args.clone())
+                .collect();
+
+            let mut directives: Vec<(&Directive, bool)> = server
+                .find_all(kind.directive())
+                .into_iter()
+                .map(|x| (x, false))
+                .collect();
+
+            // Locations without their own log inherit the server's, or the http block's.
+            let inherits = server.find(kind.directive()).is_empty();
+
+            if inherits {
+                if let Some(main) = main {
+                    directives.extend(inherited(main, kind).into_iter().map(|x| (x, true)));
+                }
+            }
+
+            let mut paths: Vec<(PathBuf, bool)> = directives
+                .into_iter()
+                .filter_map(|(directive, inherited)| Some((directive.arg(0)?, inherited)))
+                .flat_map(|(path, inherited)| {
+                    resolve(path, &names, prefix)
+                        .into_iter()
+                        .map(move |x| (x, inherited))
+                })
+                .collect();
+
+            if inherits && main.is_some() && paths.is_empty() {
+                let default = match kind {
+                    LogKind::Access => DEFAULT_ACCESS_LOG,
+                    LogKind::Error => DEFAULT_ERROR_LOG,
+                };
+
+                paths.extend(
+                    resolve(default, &names, prefix)
+                        .into_iter()
+                        .map(|x| (x, true)),
+                );
+            }
+
+            for (file_path, shared) in paths {
+                match logs.iter_mut().find(|x| x.file_path == file_path) {
+                    Some(log) => log.shared |= shared,
+                    None => logs.push(SiteLog {
+                        kind,
+                        file_path,
+                        shared,
+                    }),
+                }
+            }
+        }
+    }
+
+    logs
+}
+
+/// `access_log`/`error_log` of the `http` block, `error_log` falls back to the main
+/// context.
+fn inherited(
+    main: &ConfigFile,
+    kind: LogKind,
+) -> Vec<&Directive> {
+    let directives = main.directives();
+    let http = directives.iter().find(|x| x.name == "http");
+
+    let found = http.map(|x| x.find(kind.directive())).unwrap_or_default();
+
+    if !found.is_empty() || kind == LogKind::Access {
+        return found;
+    }
+
+    directives
+        .into_iter()
+        .filter(|x| x.name == kind.directive())
+        .collect()
+}
+
+/// Files a log path resolves to. Relative paths are resolved against `prefix`,
+/// `$host` and `$server_name` are replaced by each server name and other variables are
+/// matched against existing files. `off`, syslog and memory logs have no file.
+fn resolve(
+    path: &str,
+    server_names: &[String],
+    prefix: &Path,
+) -> Vec<PathBuf> {
+    if path == "off"
+        || path == "stderr"
+        || path.starts_with("syslog:")
+        || path.starts_with("memory:")
+    {
+        return vec![];
+    }
+
+    let path = match Path::new(path).is_absolute() {
+        true => PathBuf::from(path),
+        false => prefix.join(path),
+    };
+    let path = path.to_string_lossy().into_owned();
+
+    if !path.contains('$') {
+        return vec![PathBuf::from(path)];
+    }
+
+    let mut paths: Vec<String> = vec![];
+
+    for name in server_names.iter().filter(|x| is_plain_name(x)) {
+        let path = ["$host", "$server_name", "$http_host"]
+            .iter()
+            .fold(path.clone(), |path, variable| path.replace(variable, name));
+
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(path);
+    }
+
+    paths
+        .iter()
+        .flat_map(|path| match path.contains('$') {
+            true => glob_variables(path),
+            false => vec![PathBuf::from(path)],
+        })
+        .collect()
+}
+
+/// Exact names, not `*.example.com`, `~^regex` or the `_` catch-all.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "_" && !name.starts_with('~') && !name.contains('*')
+}
+
+/// Existing files matching `path` with its variables as wildcards.
+fn glob_variables(path: &str) -> Vec<PathBuf> {
+    let mut pattern = String::new();
+    let mut chars = path.chars().peekable();
+
+    while let Some(char) = chars.next() {
+        if char != '$' {
+            pattern.push_str(&glob::Pattern::escape(&char.to_string()));
+            continue;
+        }
+
+        let braced = chars.next_if_eq(&'{').is_some();
+        while chars
+            .next_if(|x| x.is_ascii_alphanumeric() || *x == '_')
+            .is_some()
+        {}
+
+        if braced {
+            chars.next_if_eq(&'}');
+        }
+
+        pattern.push('*');
+    }
+
+    glob::glob(&pattern)
+        .map(|paths| paths.filter_map(|x| x.ok()).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,203 @@
+use crate::config::CONFIG;
+use crate::layout::enabled_path;
+use crate::utils::{nginx_conf_file, test_nginx_config};
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+use std::fs::{self, read_link};
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// Copy of the nginx config tree with one file replaced, so a change can be checked
+/// with `nginx -t` before it touches the live config.
+#[derive(Debug)]
+pub struct StagedConfig {
+    /// Holds the copy, removed when dropped.
+    _root: TempDir,
+    /// Live directory and its copy, longest live path first.
+    mappings: Vec<(PathBuf, PathBuf)>,
+}
+
+impl StagedConfig {
+    pub fn new(
+        file_path: &Path,
+        content: &[u8],
+    ) -> Result<Self> {
+        let root = tempfile::Builder::new()
+            .prefix("ngsite-staging-")
+            .tempdir()?;
+
+        let nginx = PathBuf::from(&CONFIG.paths().nginx);
+        let mut mappings = vec![(nginx.clone(), root.path().join("nginx"))];
+
+        for (dir, name) in [
+            (&CONFIG.paths().sites_available, "sites-available"),
+            (&CONFIG.paths().sites_enabled, "sites-enabled"),
+        ] {
+            let dir = PathBuf::from(dir);
+
+            if !dir.starts_with(&nginx) {
+                mappings.push((dir, root.path().join(name)));
+            }
+        }
+
+        mappings.sort_by_key(|(live, _)| Reverse(live.as_os_str().len()));
+
+        let staged = Self {
+            _root: root,
+            mappings,
+        };
+
+        for (live, copy) in &staged.mappings {
+            staged.copy_tree(live, copy)?;
+        }
+
+        let staged_file = staged.to_staged(file_path);
+        fs::write(&staged_file, content)?;
+        staged.include_site(file_path)?;
+
+        Ok(staged)
+    }
+
+    /// Runs `nginx -t` against the staged tree, reporting live paths in the output.
+    pub fn test(&self) -> Result<()> {
+        let conf_file = self.to_staged(&nginx_conf_file());
+
+        test_nginx_config(&conf_file).map_err(|err| anyhow!(self.to_live(&err.to_string())))
+    }
+
+    /// Links a site into where it is included from once enabled, so it is tested even
+    /// when it is not enabled yet.
+    fn include_site(
+        &self,
+        file_path: &Path,
+    ) -> Result<()> {
+        let Some(enabled_path) = enabled_path(file_path) else {
+            return Ok(());
+        };
+
+        let link = self.to_staged(&enabled_path);
+
+        if link.symlink_metadata().is_err() {
+            fs::create_dir_all(link.parent().unwrap())?;
+            symlink(self.to_staged(file_path), link)?;
+        }
+
+        Ok(())
+    }
+
+    fn copy_tree(
+        &self,
+        live: &Path,
+        copy: &Path,
+    ) -> Result<()> {
+        fs::create_dir_all(copy)?;
+
+        if !live.exists() {
+            return Ok(());
+        }
+
+        for entry in WalkDir::new(live).min_depth(1) {
+            let entry = entry?;
+            let target = copy.join(entry.path().strip_prefix(live)?);
+
+            if entry.path_is_symlink() {
+                let link = read_link(entry.path())?;
+                let link = if link.is_absolute() {
+                    self.to_staged(&link)
+                } else {
+                    link
+                };
+
+                symlink(link, target)?;
+            } else if entry.file_type().is_dir() {
+                fs::create_dir_all(target)?;
+            } else {
+                let content = fs::read(entry.path())?;
+
+                match String::from_utf8(content) {
+                    Ok(text) => fs::write(target, self.to_staged_text(&text))?,
+                    Err(err) => fs::write(target, err.into_bytes())?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_staged(
+        &self,
+        path: &Path,
+    ) -> PathBuf {
+        for (live, copy) in &self.mappings {
+            if let Ok(rest) = path.strip_prefix(live) {
+                return copy.join(rest);
+            }
+        }
+
+        path.to_path_buf()
+    }
+
+    fn to_staged_text(
+        &self,
+        text: &str,
+    ) -> String {
+        let mut text = text.to_string();
+
+        for (live, copy) in &self.mappings {
+            text = replace_path(&text, &live.to_string_lossy(), &copy.to_string_lossy());
+        }
+
+        text
+    }
+
+    fn to_live(
+        &self,
+        text: &str,
+    ) -> String {
+        let mut text = text.to_string();
+
+        for (live, copy) in &self.mappings {
+            text = text.replace(&*copy.to_string_lossy(), &live.to_string_lossy());
+        }
+
+        text
+    }
+}
+
+/// Replaces `from` where it is a whole path or the start of one, so `/etc/nginx` is
+/// replaced in `/etc/nginx/mime.types` but not in `/etc/nginx-edge/mime.types`.
+fn replace_path(
+    text: &str,
+    from: &str,
+    to: &str,
+) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(from) {
+        let end = start + from.len();
+        let starts_path = rest[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|x| !is_path_char(x));
+        let ends_path = rest[end..]
+            .chars()
+            .next()
+            .is_none_or(|x| x == '/' || !is_path_char(x));
+
+        output.push_str(&rest[..start]);
+        output.push_str(if starts_path && ends_path { to } else { from });
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+
+    output
+}
+
+/// Characters that can't end an unquoted path in a config file.
+fn is_path_char(char: char) -> bool {
+    !char.is_whitespace() && !matches!(char, ';' | '"' | '\'' | '{' | '}')
+}
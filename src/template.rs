@@ -0,0 +1,236 @@
+use crate::config::{templates_dir, CONFIG};
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Input};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub type TemplateValues = HashMap<String, String>;
+
+const MANIFEST_FILE: &str = "template.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateVar {
+    pub name: String,
+    #[serde(default)]
+    pub prompt: String,
+    /// May reference variables declared before this one, e.g. `www.{{domain}}`.
+    #[serde(default)]
+    pub default: String,
+}
+
+impl TemplateVar {
+    pub fn new(
+        name: impl Into<String>,
+        prompt: impl Into<String>,
+        default: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            prompt: prompt.into(),
+            default: default.into(),
+        }
+    }
+}
+
+/// `template.toml` of a user template, e.g. `~/.config/Ngsite/templates/spa/template.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateManifest {
+    /// Defaults to the template's directory name.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Relative paths are resolved against `paths.nginx`, which it can't lead out of.
+    pub target_dir: String,
+    /// Name of the created file, may use variables, e.g. `{{domain}}`, but no `/`.
+    pub file_name: String,
+    /// Template body, relative to the template's directory.
+    #[serde(default = "default_template_file")]
+    pub template: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVar>,
+}
+
+fn default_template_file() -> String {
+    "template.conf".into()
+}
+
+#[derive(Debug, Clone)]
+pub struct UserTemplate {
+    pub manifest: TemplateManifest,
+    pub body: Vec<u8>,
+}
+
+/// Collects a value for every variable, in order. Values in `preset` are used as is,
+/// the rest are prompted for when `interactive`, otherwise their default is used.
+pub fn resolve_vars(
+    vars: &[TemplateVar],
+    preset: &TemplateValues,
+    interactive: bool,
+) -> Result<TemplateValues> {
+    let mut values = TemplateValues::new();
+
+    for var in vars {
+        if let Some(value) = preset.get(&var.name) {
+            values.insert(var.name.clone(), value.clone());
+            continue;
+        }
+
+        let default = render(&var.default, &values)?;
+        let prompt = if var.prompt.is_empty() {
+            &var.name
+        } else {
+            &var.prompt
+        };
+
+        let value = if interactive {
+            Input::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(default)
+                .interact_text()?
+        } else {
+            default
+        };
+
+        values.insert(var.name.clone(), value);
+    }
+
+    Ok(values)
+}
+
+/// Replaces every `{{name}}` in `template` with its value.
+pub fn render(
+    template: &str,
+    values: &TemplateValues,
+) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| anyhow!("Unclosed template variable: {}", &rest[start..]))?;
+
+        let name = after[..end].trim();
+        let value = values
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown template variable: {name}"))?;
+
+        output.push_str(value);
+        rest = &after[end + 2..];
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Names of the variables used in `template`, in order.
+pub fn used_vars(template: &str) -> Vec<&str> {
+    let mut names: Vec<&str> = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+
+        names.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+
+    names
+}
+
+/// Whether `name` is a single file name, which can't lead out of its directory.
+pub fn is_file_name(name: &str) -> bool {
+    let components: Vec<Component> = Path::new(name).components().collect();
+
+    !name.contains('/') && matches!(components[..], [Component::Normal(_)])
+}
+
+/// Parses a `KEY=VALUE` command line argument.
+pub fn parse_key_val(arg: &str) -> Result<(String, String)> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected KEY=VALUE, got {arg:?}"))?;
+
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+/// Loads every template found in the templates directory, skipping invalid ones.
+pub fn load_user_templates() -> Vec<UserTemplate> {
+    let mut templates: Vec<UserTemplate> = vec![];
+
+    let Some(dir) = templates_dir() else {
+        return templates;
+    };
+
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return templates;
+    };
+
+    for entry in entries.flatten() {
+        let template_dir = entry.path();
+
+        if !template_dir.join(MANIFEST_FILE).exists() {
+            continue;
+        }
+
+        match load_user_template(&template_dir) {
+            Ok(template) => templates.push(template),
+            Err(err) => warn!("Skipping template {:?}: {err:#}", template_dir),
+        }
+    }
+
+    templates.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+
+    templates
+}
+
+fn load_user_template(template_dir: &Path) -> Result<UserTemplate> {
+    let manifest_path = template_dir.join(MANIFEST_FILE);
+    let manifest = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {:?}", manifest_path))?;
+    let mut manifest: TemplateManifest = toml::from_str(&manifest)?;
+
+    if manifest.name.is_empty() {
+        manifest.name = template_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into();
+    }
+
+    let nginx_dir = Path::new(&CONFIG.paths().nginx);
+    let target_dir = nginx_dir.join(&manifest.target_dir);
+
+    if !target_dir.starts_with(nginx_dir)
+        || target_dir.components().any(|x| x == Component::ParentDir)
+    {
+        return Err(anyhow!(
+            "target_dir {:?} is outside {}",
+            manifest.target_dir,
+            nginx_dir.display()
+        ));
+    }
+
+    if !is_file_name(&manifest.file_name) {
+        return Err(anyhow!(
+            "file_name {:?} is not a file name",
+            manifest.file_name
+        ));
+    }
+
+    let body_path: PathBuf = template_dir.join(&manifest.template);
+    let body = fs::read(&body_path).with_context(|| format!("Failed to read {:?}", body_path))?;
+
+    Ok(UserTemplate { manifest, body })
+}
@@ -0,0 +1,391 @@
+use crate::config::CONFIG;
+use crate::utils::test_nginx;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs::{self, read_link, remove_file, File};
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Records the symlinks of sites-enabled (and the content of edited files and renamed
+/// sites) so they can be restored exactly when `nginx -t` fails after a change.
+#[derive(Debug)]
+pub struct SitesTransaction {
+    enabled_dir: PathBuf,
+    links: HashMap<String, PathBuf>,
+    files: Vec<(PathBuf, Vec<u8>)>,
+    created: Vec<PathBuf>,
+    renames: Vec<(PathBuf, PathBuf)>,
+    changed: Vec<String>,
+}
+
+impl SitesTransaction {
+    pub fn begin() -> Result<Self> {
+        Self::begin_in(PathBuf::from(&CONFIG.paths().sites_enabled))
+    }
+
+    fn begin_in(enabled_dir: PathBuf) -> Result<Self> {
+        Ok(Self {
+            links: read_links(&enabled_dir)?,
+            enabled_dir,
+            files: vec![],
+            created: vec![],
+            renames: vec![],
+            changed: vec![],
+        })
+    }
+
+    /// Marks `site` as changed by this transaction.
+    pub fn touch(
+        &mut self,
+        site: impl Into<String>,
+    ) {
+        self.changed.push(site.into());
+    }
+
+    /// Keeps the current content of `file_path` so it can be restored on rollback.
+    pub fn backup_file(
+        &mut self,
+        file_path: impl Into<PathBuf>,
+    ) -> Result<()> {
+        let file_path = file_path.into();
+        let content = fs::read(&file_path)?;
+
+        self.files.push((file_path, content));
+
+        Ok(())
+    }
+
+    /// Records a file created by the change, so it is removed on rollback.
+    pub fn created_file(
+        &mut self,
+        file_path: impl Into<PathBuf>,
+    ) {
+        self.created.push(file_path.into());
+    }
+
+    /// Renames `from` to `to`, renaming it back on rollback. Fails when `to` exists, a
+    /// rollback couldn't bring it back.
+    pub fn rename(
+        &mut self,
+        from: impl Into<PathBuf>,
+        to: impl Into<PathBuf>,
+    ) -> Result<()> {
+        let (from, to) = (from.into(), to.into());
+
+        if to.symlink_metadata().is_ok() {
+            return Err(anyhow!(
+                "{} already exists, remove or rename it first",
+                to.display()
+            ));
+        }
+
+        fs::rename(&from, &to)?;
+        self.renames.push((from, to));
+
+        Ok(())
+    }
+
+    /// Runs `nginx -t`, rolls back every change if it fails.
+    pub fn commit(self) -> Result<()> {
+        self.finish(test_nginx())
+    }
+
+    /// Rolls back every change if `tested` failed.
+    fn finish(
+        self,
+        tested: Result<()>,
+    ) -> Result<()> {
+        let Err(err) = tested else {
+            return Ok(());
+        };
+
+        let culprit = self.find_culprit(&err.to_string());
+
+        self.rollback()?;
+
+        match culprit {
+            Some(site) => Err(anyhow!(
+                "{site} failed nginx test, changes rolled back\n{err}"
+            )),
+            None => Err(anyhow!(
+                "Nginx test failed after changing {}, changes rolled back\n{err}",
+                self.changed.join(", ")
+            )),
+        }
+    }
+
+    /// Restores the recorded symlinks, names and files.
+    pub fn rollback(&self) -> Result<()> {
+        for (from, to) in self.renames.iter().rev() {
+            fs::rename(to, from)?;
+        }
+
+        let enabled_dir = &self.enabled_dir;
+        let current = read_links(enabled_dir)?;
+
+        for (name, target) in &current {
+            if self.links.get(name) != Some(target) {
+                remove_file(enabled_dir.join(name))?;
+            }
+        }
+
+        for (name, target) in &self.links {
+            if current.get(name) != Some(target) {
+                let link = enabled_dir.join(name);
+
+                if link.symlink_metadata().is_ok() {
+                    remove_file(&link)?;
+                }
+
+                if let Some(parent) = link.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                symlink(target, link)?;
+            }
+        }
+
+        for file_path in &self.created {
+            if file_path.exists() {
+                remove_file(file_path)?;
+            }
+        }
+
+        for (file_path, content) in &self.files {
+            // Gone when the change replaced it, e.g. with a link.
+            if file_path.is_file() {
+                let rejected = save_rejected(file_path)?;
+                warn!("Rejected changes saved to {:?}", rejected);
+            }
+
+            fs::write(file_path, content)?;
+        }
+
+        warn!("Rolled back {}", self.changed.join(", "));
+
+        Ok(())
+    }
+
+    /// Finds the changed site named in the output of `nginx -t`, e.g.
+    /// `nginx: [emerg] unknown directive "foo" in /etc/nginx/sites-enabled/example.com:12`.
+    fn find_culprit(
+        &self,
+        output: &str,
+    ) -> Option<String> {
+        for line in output.lines() {
+            let Some((_, location)) = line.rsplit_once(" in ") else {
+                continue;
+            };
+
+            let file = location.rsplit_once(':').map_or(location, |(file, _)| file);
+            let file = Path::new(file);
+
+            if let Some(site) = self.changed.iter().find(|x| file.ends_with(x)) {
+                return Some(site.clone());
+            }
+        }
+
+        None
+    }
+}
+
+fn read_links(dir: &Path) -> Result<HashMap<String, PathBuf>> {
+    let mut links: HashMap<String, PathBuf> = HashMap::new();
+
+    if !dir.exists() {
+        return Ok(links);
+    }
+
+    let max_depth = if CONFIG.paths().recursive {
+        usize::MAX
+    } else {
+        1
+    };
+
+    for entry in WalkDir::new(dir).min_depth(1).max_depth(max_depth) {
+        let entry = entry?;
+
+        if entry.path_is_symlink() {
+            let name: String = entry.path().strip_prefix(dir)?.to_string_lossy().into();
+            links.insert(name, read_link(entry.path())?);
+        }
+    }
+
+    Ok(links)
+}
+
+/// Copies `file_path` to a new file in the temp directory, created exclusively so a
+/// link planted there can't redirect the copy.
+fn save_rejected(file_path: &Path) -> Result<PathBuf> {
+    let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+    let mut rejected = tempfile::Builder::new()
+        .prefix(&format!("{file_name}."))
+        .suffix(".rejected")
+        .tempfile()?;
+
+    io::copy(&mut File::open(file_path)?, rejected.as_file_mut())?;
+
+    let (_, rejected) = rejected.keep()?;
+
+    Ok(rejected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::replace_link;
+
+    const TEST_FAILED: &str =
+        "nginx: [emerg] unknown directive \"foo\" in /etc/nginx/sites-enabled/a.com:3";
+
+    struct Sites {
+        _dir: tempfile::TempDir,
+        available: PathBuf,
+        enabled: PathBuf,
+    }
+
+    fn sites() -> Sites {
+        let dir = tempfile::tempdir().unwrap();
+        let available = dir.path().join("sites-available");
+        let enabled = dir.path().join("sites-enabled");
+
+        fs::create_dir(&available).unwrap();
+        fs::create_dir(&enabled).unwrap();
+
+        for name in ["a.com", "b.com"] {
+            fs::write(
+                available.join(name),
+                format!("server {{ server_name {name}; }}"),
+            )
+            .unwrap();
+        }
+
+        Sites {
+            _dir: dir,
+            available,
+            enabled,
+        }
+    }
+
+    #[test]
+    fn restores_links_when_the_test_fails() {
+        let sites = sites();
+        symlink(sites.available.join("a.com"), sites.enabled.join("a.com")).unwrap();
+
+        let mut transaction = SitesTransaction::begin_in(sites.enabled.clone()).unwrap();
+        transaction.touch("a.com");
+        transaction.touch("b.com");
+
+        remove_file(sites.enabled.join("a.com")).unwrap();
+        replace_link(&sites.available.join("b.com"), &sites.enabled.join("b.com")).unwrap();
+
+        let err = transaction.finish(Err(anyhow!(TEST_FAILED))).unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("a.com failed nginx test"),
+            "{err}"
+        );
+        assert_eq!(
+            read_link(sites.enabled.join("a.com")).unwrap(),
+            sites.available.join("a.com")
+        );
+        assert!(sites.enabled.join("b.com").symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn keeps_changes_when_the_test_passes() {
+        let sites = sites();
+
+        let mut transaction = SitesTransaction::begin_in(sites.enabled.clone()).unwrap();
+        transaction.touch("a.com");
+        replace_link(&sites.available.join("a.com"), &sites.enabled.join("a.com")).unwrap();
+
+        transaction.finish(Ok(())).unwrap();
+
+        assert!(sites.enabled.join("a.com").is_symlink());
+    }
+
+    #[test]
+    fn restores_a_copied_entry_replaced_with_a_link() {
+        let sites = sites();
+        let copied = sites.enabled.join("a.com");
+        fs::write(&copied, "server { server_name old.a.com; }").unwrap();
+
+        // Enabling refuses to remove the copy, a rollback couldn't restore it.
+        let mut transaction = SitesTransaction::begin_in(sites.enabled.clone()).unwrap();
+        let err = replace_link(&sites.available.join("a.com"), &copied).unwrap_err();
+
+        assert!(err.to_string().contains("run doctor"), "{err}");
+        assert_eq!(
+            fs::read_to_string(&copied).unwrap(),
+            "server { server_name old.a.com; }"
+        );
+
+        // Doctor backs it up before linking.
+        transaction.touch("a.com");
+        transaction.backup_file(&copied).unwrap();
+        remove_file(&copied).unwrap();
+        replace_link(&sites.available.join("a.com"), &copied).unwrap();
+
+        transaction.finish(Err(anyhow!(TEST_FAILED))).unwrap_err();
+
+        assert!(!copied.is_symlink());
+        assert_eq!(
+            fs::read_to_string(&copied).unwrap(),
+            "server { server_name old.a.com; }"
+        );
+        assert_eq!(
+            fs::read_to_string(sites.available.join("a.com")).unwrap(),
+            "server { server_name a.com; }"
+        );
+    }
+
+    #[test]
+    fn undoes_renames_and_created_files() {
+        let sites = sites();
+        let conf = sites.enabled.join("c.conf");
+        let disabled = sites.enabled.join("c.conf.disabled");
+        let created = sites.available.join("c.com");
+        fs::write(&disabled, "server {}").unwrap();
+
+        let mut transaction = SitesTransaction::begin_in(sites.enabled.clone()).unwrap();
+        transaction.touch("c.conf");
+        transaction.rename(&disabled, &conf).unwrap();
+        fs::write(&created, "server {}").unwrap();
+        transaction.created_file(&created);
+
+        let err = transaction
+            .finish(Err(anyhow!("nginx: [emerg] no \"events\" section")))
+            .unwrap_err();
+
+        assert!(
+            err.to_string()
+                .starts_with("Nginx test failed after changing c.conf"),
+            "{err}"
+        );
+        assert!(disabled.exists());
+        assert!(!conf.exists());
+        assert!(!created.exists());
+    }
+
+    #[test]
+    fn never_renames_over_a_file() {
+        let sites = sites();
+        let conf = sites.enabled.join("c.conf");
+        let disabled = sites.enabled.join("c.conf.disabled");
+        fs::write(&conf, "server { listen 80; }").unwrap();
+        fs::write(&disabled, "server { listen 8080; }").unwrap();
+
+        let mut transaction = SitesTransaction::begin_in(sites.enabled.clone()).unwrap();
+        let err = transaction.rename(&disabled, &conf).unwrap_err();
+
+        assert!(err.to_string().contains("already exists"), "{err}");
+        assert_eq!(fs::read_to_string(&conf).unwrap(), "server { listen 80; }");
+        assert_eq!(
+            fs::read_to_string(&disabled).unwrap(),
+            "server { listen 8080; }"
+        );
+    }
+}
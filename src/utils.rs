@@ -1,20 +1,18 @@
 use crate::config::CONFIG;
 use anyhow::{anyhow, Context, Result};
-use async_compression::tokio::bufread::GzipDecoder;
-use async_recursion::async_recursion;
+use dialoguer::Editor;
 use env_logger::fmt::Color;
 use log::Level;
-use minus::{page_all, ExitStrategy, LineNumbers, MinusError, Pager};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::env::{set_var, var_os};
+use std::env::{set_var, var, var_os};
 use std::fs::remove_file;
 use std::io::{ErrorKind, Write};
 use std::os::unix::fs::symlink;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use tokio::fs::{create_dir_all, File};
-use tokio::io::{AsyncReadExt, BufReader};
+use tokio::io::AsyncReadExt;
 use walkdir::WalkDir;
 use which::which;
 
@@ -26,6 +24,25 @@ pub struct FileData {
 }
 
 pub async fn walk_folder(folder: &str) -> Result<HashMap<String, FileData>> {
+    walk_tree(folder, 1).await
+}
+
+/// Like `walk_folder`, but also walks sub directories when `paths.recursive` is set.
+/// Files are keyed by their path relative to `folder`, e.g. `clients/acme.conf`.
+pub async fn walk_sites_folder(folder: &str) -> Result<HashMap<String, FileData>> {
+    let max_depth = if CONFIG.paths().recursive {
+        usize::MAX
+    } else {
+        1
+    };
+
+    walk_tree(folder, max_depth).await
+}
+
+async fn walk_tree(
+    folder: &str,
+    max_depth: usize,
+) -> Result<HashMap<String, FileData>> {
     let mut files: HashMap<String, FileData> = HashMap::new();
     let dir = Path::new(folder);
 
@@ -33,10 +50,10 @@ pub async fn walk_folder(folder: &str) -> Result<HashMap<String, FileData>> {
         create_dir_all(&dir).await?;
     }
 
-    let iter = WalkDir::new(dir).max_depth(1);
+    let iter = WalkDir::new(dir).max_depth(max_depth);
     for entry in iter {
         let entry = entry?;
-        let file_name: String = entry.file_name().to_string_lossy().into();
+        let file_name: String = entry.path().strip_prefix(dir)?.to_string_lossy().into();
         let file_path: String = entry.path().to_string_lossy().into();
         let is_symlink = entry.path_is_symlink();
         let is_dir = entry.into_path().is_dir();
@@ -58,10 +75,9 @@ pub async fn walk_folder(folder: &str) -> Result<HashMap<String, FileData>> {
     Ok(files)
 }
 
-#[async_recursion]
 pub async fn sym_link(file: String) -> Result<()> {
-    let available_dir = Path::new(&CONFIG.paths.sites_available);
-    let enabled_dir = Path::new(&CONFIG.paths.sites_enabled);
+    let available_dir = Path::new(&CONFIG.paths().sites_available);
+    let enabled_dir = Path::new(&CONFIG.paths().sites_enabled);
 
     if !enabled_dir.exists() {
         create_dir_all(&enabled_dir).await?;
@@ -80,35 +96,63 @@ pub async fn sym_link(file: String) -> Result<()> {
         return Ok(());
     }
 
-    let symlink_res = symlink(&available_path, &enabled_path);
+    if let Some(parent) = enabled_path.parent() {
+        create_dir_all(parent).await?;
+    }
+
+    replace_link(&available_path, &enabled_path)
+}
+
+/// Links `enabled_path` to `available_path`, replacing a link but never a file, which
+/// a rollback couldn't bring back.
+pub fn replace_link(
+    available_path: &Path,
+    enabled_path: &Path,
+) -> Result<()> {
+    if let Err(err) = symlink(available_path, enabled_path) {
+        if err.kind() != ErrorKind::AlreadyExists {
+            error!("Failed to symlink");
+            return Err(err.into());
+        }
 
-    if let Err(err) = symlink_res {
-        if err.kind() == ErrorKind::AlreadyExists {
-            remove_file(enabled_path)?;
-            return sym_link(file).await;
+        if !enabled_path.is_symlink() {
+            return Err(anyhow!(
+                "{} is a file, not a link, run doctor to fix it",
+                enabled_path.display()
+            ));
         }
 
-        error!("Failed to symlink");
-        return Err(err.into());
+        remove_file(enabled_path)?;
+        symlink(available_path, enabled_path)?;
     }
 
     Ok(())
 }
 
 pub async fn rm_symlink(file_name: String) -> Result<()> {
-    let enabled_dir = Path::new(&CONFIG.paths.sites_enabled);
+    let enabled_dir = Path::new(&CONFIG.paths().sites_enabled);
     let file_path = enabled_dir.join(file_name);
 
-    if file_path.exists() {
+    if file_path.is_symlink() {
         remove_file(file_path)?;
+    } else if file_path.exists() {
+        return Err(anyhow!(
+            "{} is a file, not a link, run doctor to fix it",
+            file_path.display()
+        ));
     }
 
     Ok(())
 }
 
 pub fn test_nginx() -> Result<()> {
-    let nginx_path = get_command_path("nginx")?;
-    let output = Command::new(nginx_path).arg("-t").output()?;
+    test_nginx_config(&nginx_conf_file())
+}
+
+/// Runs `nginx -t` against `conf_file` instead of the configured nginx.conf.
+pub fn test_nginx_config(conf_file: &Path) -> Result<()> {
+    let mut command = nginx_command_with_conf(conf_file)?;
+    let output = command.arg("-t").output()?;
 
     if !output.status.success() {
         error!("Nginx test failed");
@@ -120,51 +164,131 @@ pub fn test_nginx() -> Result<()> {
     Ok(())
 }
 
-pub fn reload_nginx() -> Result<()> {
-    let systemctl_path = get_command_path("systemctl")?;
-    let output = Command::new(systemctl_path)
-        .arg("reload")
-        .arg("nginx")
-        .output()?;
+/// `paths.nginx_conf`, defaults to nginx.conf in `paths.nginx`.
+pub fn nginx_conf_file() -> PathBuf {
+    match &CONFIG.paths().nginx_conf {
+        Some(conf_file) => PathBuf::from(conf_file),
+        None => Path::new(&CONFIG.paths().nginx).join("nginx.conf"),
+    }
+}
 
-    if !output.status.success() {
-        error!("Nginx reload  failed");
+/// nginx's prefix: `prefix` of the config, the `--prefix` nginx was built with or the
+/// directory above the config directory, as in source builds (/usr/local/nginx/conf).
+pub fn nginx_prefix() -> PathBuf {
+    let paths = CONFIG.paths();
 
-        let err = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(anyhow!(err));
+    if let Some(prefix) = &paths.prefix {
+        return PathBuf::from(prefix);
     }
 
-    info!("Nginx reloaded successfully");
-    Ok(())
+    nginx_built_with("--prefix")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            Path::new(&paths.nginx)
+                .parent()
+                .map(|x| x.to_path_buf())
+                .unwrap_or_default()
+        })
 }
 
-pub fn edit_nginx_site(file_name: String) -> Result<()> {
-    let available_dir = Path::new(&CONFIG.paths.sites_available);
-    let file_path = available_dir.join(file_name);
+/// A configure argument of the nginx binary, e.g. `--pid-path`, `None` when it wasn't
+/// set or nginx can't be run.
+pub fn nginx_built_with(name: &str) -> Option<String> {
+    let output = get_command_path(&CONFIG.paths().nginx_bin)
+        .and_then(|x| Ok(Command::new(x).arg("-V").output()?))
+        .ok()?;
+
+    // `nginx -V` prints its configure arguments to stderr.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let flag = format!("{name}=");
+
+    stderr
+        .split_whitespace()
+        .find_map(|x| x.strip_prefix(flag.as_str()))
+        .map(String::from)
+}
 
-    if !file_path.exists() {
-        info!("File not found.");
+/// The configured nginx binary with `-c` and, when set, `-p`.
+pub fn nginx_command() -> Result<Command> {
+    nginx_command_with_conf(&nginx_conf_file())
+}
 
-        return Ok(());
+fn nginx_command_with_conf(conf_file: &Path) -> Result<Command> {
+    let mut command = Command::new(get_command_path(&CONFIG.paths().nginx_bin)?);
+
+    if let Some(prefix) = &CONFIG.paths().prefix {
+        command.arg("-p").arg(prefix);
     }
 
-    let vi_path = get_command_path("vi")?;
-    let output = Command::new(vi_path).arg(file_path).status()?;
+    command.arg("-c").arg(conf_file);
 
-    if !output.success() {
-        error!("Edit failed");
+    Ok(command)
+}
 
-        let err = output.to_string();
-        return Err(anyhow!(err));
+pub fn reload_nginx() -> Result<()> {
+    if let Err(err) = CONFIG.service().reload() {
+        error!("Nginx reload  failed");
+        return Err(err);
     }
 
-    info!("Edit done.");
+    info!("Nginx reloaded successfully");
     Ok(())
 }
 
-pub async fn view_nginx_site(file_name: String) -> Result<()> {
-    let available_dir = Path::new(&CONFIG.paths.sites_available);
-    let file_path = available_dir.join(&file_name);
+pub fn restart_nginx() -> Result<()> {
+    if let Err(err) = CONFIG.service().restart() {
+        error!("Nginx restart failed");
+        return Err(err);
+    }
+
+    info!("Nginx restarted successfully");
+    Ok(())
+}
+
+/// Opens `content` in the editor, returns `None` when it was closed without saving.
+pub fn edit_text(content: &str) -> Result<Option<String>> {
+    let editor = get_editor();
+    let words = shell_words::split(&editor).context(format!("Invalid editor: {editor}"))?;
+    let program = words.first().context("Editor is empty")?;
+
+    get_command_path(program)?;
+
+    let edited = Editor::new()
+        .executable(&editor)
+        .extension(".conf")
+        .require_save(true)
+        .trim_newlines(false)
+        .edit(content);
+
+    match edited {
+        Ok(edited) => {
+            info!("Edit done.");
+            Ok(edited)
+        }
+        Err(err) => {
+            error!("Edit failed");
+            Err(err.into())
+        }
+    }
+}
+
+/// `editor` from the config, then `$VISUAL`, `$EDITOR` and finally `vi`.
+/// May include arguments, e.g. `code --wait`.
+pub fn get_editor() -> String {
+    CONFIG
+        .editor
+        .clone()
+        .filter(|x| !x.trim().is_empty())
+        .or_else(|| var("VISUAL").ok().filter(|x| !x.trim().is_empty()))
+        .or_else(|| var("EDITOR").ok().filter(|x| !x.trim().is_empty()))
+        .unwrap_or_else(|| "vi".into())
+}
+
+pub async fn view_nginx_site(
+    file_name: &str,
+    file_path: &str,
+) -> Result<()> {
+    let file_path = Path::new(file_path);
 
     if !file_path.exists() {
         info!("File not found.");
@@ -183,47 +307,34 @@ pub async fn view_nginx_site(file_name: String) -> Result<()> {
     Ok(())
 }
 
-pub async fn view_log_file(file_name: String) -> Result<()> {
-    let logs_dir = Path::new(&CONFIG.paths.logs);
-    let file_path = logs_dir.join(&file_name);
-
-    if !file_path.exists() {
-        info!("File not found.");
+/// Prints `rows` as left aligned columns, missing cells are left blank.
+pub fn print_table(
+    headers: &[&str],
+    rows: &[Vec<String>],
+) {
+    let mut widths: Vec<usize> = headers.iter().map(|x| x.chars().count()).collect();
 
-        return Ok(());
+    for row in rows {
+        for (index, cell) in row.iter().enumerate().take(widths.len()) {
+            widths[index] = widths[index].max(cell.chars().count());
+        }
     }
 
-    let log = if is_gzip(file_path.to_string_lossy())? {
-        read_gzip_log(file_path).await?
-    } else {
-        read_log(file_path).await?
-    };
-
-    cli_pager(log, &file_name).await?;
-
-    Ok(())
-}
-
-async fn read_gzip_log(file_path: PathBuf) -> Result<String> {
-    let mut buffer = String::new();
-
-    let file = File::open(file_path).await?;
-    let file = BufReader::new(file);
-    let mut file = GzipDecoder::new(file);
-
-    file.read_to_string(&mut buffer).await?;
-
-    Ok(buffer)
-}
-
-async fn read_log(file_path: PathBuf) -> Result<String> {
-    let mut buffer = String::new();
+    let format_row = |cells: Vec<&str>| -> String {
+        let line: Vec<String> = widths
+            .iter()
+            .enumerate()
+            .map(|(index, width)| format!("{:width$}", cells.get(index).unwrap_or(&"")))
+            .collect();
 
-    let mut file = File::open(file_path).await?;
+        line.join("  ").trim_end().to_string()
+    };
 
-    file.read_to_string(&mut buffer).await?;
+    println!("{}", format_row(headers.to_vec()));
 
-    Ok(buffer)
+    for row in rows {
+        println!("{}", format_row(row.iter().map(|x| x.as_str()).collect()));
+    }
 }
 
 pub fn init_logger() {
@@ -278,44 +389,3 @@ pub fn merge_config(
 pub fn is_root() -> bool {
     users::get_current_uid() == 0
 }
-
-pub fn is_gzip(file: impl Into<String>) -> Result<bool> {
-    let gzip_path = get_command_path("gzip")?;
-
-    let output = Command::new(gzip_path)
-        .arg("-vt")
-        .arg(&file.into())
-        .output()?;
-
-    Ok(output.status.success())
-}
-
-pub async fn cli_pager(
-    log: String,
-    prompt: &String,
-) -> Result<(), MinusError> {
-    let pager = Pager::new();
-
-    pager.set_exit_strategy(ExitStrategy::PagerQuit)?;
-    pager.set_line_numbers(LineNumbers::AlwaysOn)?;
-    pager.set_prompt(prompt)?;
-    pager.set_run_no_overflow(true)?;
-
-    let ignore_values_in_log = CONFIG.ignore_values_in_log.iter();
-    let ignore_values_in_log_ref = ignore_values_in_log.as_ref();
-
-    'outer: for line in log.lines().rev() {
-        for skip_value in ignore_values_in_log_ref {
-            if line.contains(skip_value) {
-                continue 'outer;
-            }
-        }
-
-        pager.push_str(line)?;
-        pager.push_str("\n")?;
-    }
-
-    page_all(pager)?;
-
-    Ok(())
-}